@@ -0,0 +1,295 @@
+// Measures decode() and decode_u16() throughput in MB/s of decoded output on
+// the bundled test images and on synthetic worst case encodings, and the
+// throughput of encoding and remapping the same images.  Run with
+// `cargo bench --bench decode`, criterion keeps the previous results in
+// target/criterion and reports any change against them.
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use dicomrle::decode::{decode, decode_into_vec, decode_u16};
+use dicomrle::decode_segment::decode_segment;
+use dicomrle::descriptor::ImageDescriptor;
+use dicomrle::encode_segment::SegmentWriter;
+use dicomrle::get_segments::get_segments;
+use dicomrle::pitched::{decode_pitched, pitched_length};
+use dicomrle::remap::remap_frame;
+use dicomrle::thumbnail::{decode_thumbnail, Downsample};
+use std::fs;
+
+// rows and columns of the synthetic images
+const SYNTHETIC_ROWS: usize = 1024;
+const SYNTHETIC_COLUMNS: usize = 1024;
+
+// builds an RLE image from its segments
+fn make_image(segments: &[Vec<u8>]) -> Vec<u8> {
+    let mut header = [0u32; 16];
+    header[0] = segments.len() as u32;
+    let mut offset = 64;
+    for (index, segment) in segments.iter().enumerate() {
+        header[index + 1] = offset;
+        offset += segment.len() as u32;
+    }
+
+    let mut encoded: Vec<u8> = header
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    for segment in segments {
+        encoded.extend_from_slice(segment);
+    }
+    encoded
+}
+
+// encodes length bytes as literal runs of 128 bytes, the longest allowed
+fn all_literal(length: usize, seed: u8) -> Vec<u8> {
+    let mut segment = Vec::new();
+    let mut remaining = length;
+    while remaining > 0 {
+        let run_length = remaining.min(128);
+        segment.push((run_length - 1) as u8);
+        for index in 0..run_length {
+            segment.push((index as u8).wrapping_mul(31).wrapping_add(seed));
+        }
+        remaining -= run_length;
+    }
+    segment
+}
+
+// encodes length bytes as replicated runs of 128 bytes, the longest allowed
+fn all_replicate(length: usize, seed: u8) -> Vec<u8> {
+    let mut segment = Vec::new();
+    let mut remaining = length;
+    while remaining > 1 {
+        let run_length = remaining.min(128);
+        segment.extend_from_slice(&[(257 - run_length) as u8, seed]);
+        remaining -= run_length;
+    }
+    if remaining == 1 {
+        segment.extend_from_slice(&[0, seed]);
+    }
+    segment
+}
+
+// encodes length bytes alternating between a literal run of one byte and a
+// replicated run of two bytes, the shortest runs there are, so the decoder
+// spends most of its time reading control bytes
+fn alternating(length: usize, seed: u8) -> Vec<u8> {
+    let mut segment = Vec::new();
+    let mut remaining = length;
+    while remaining >= 3 {
+        segment.extend_from_slice(&[0, seed, 255, seed.wrapping_add(1)]);
+        remaining -= 3;
+    }
+    for _ in 0..remaining {
+        segment.extend_from_slice(&[0, seed]);
+    }
+    segment
+}
+
+// returns the name, encoding and layout of each synthetic image, as both 8
+// bit grayscale and 16 bit grayscale
+fn synthetic_images() -> Vec<(String, Vec<u8>, ImageDescriptor)> {
+    let pixels = SYNTHETIC_ROWS * SYNTHETIC_COLUMNS;
+    let mut images = Vec::new();
+    for (name, encode) in &[
+        ("all_literal", all_literal as fn(usize, u8) -> Vec<u8>),
+        ("all_replicate", all_replicate),
+        ("alternating", alternating),
+    ] {
+        images.push((
+            format!("{}_8bit", name),
+            make_image(&[encode(pixels, 1)]),
+            ImageDescriptor::new(SYNTHETIC_ROWS, SYNTHETIC_COLUMNS, 1, 8),
+        ));
+        images.push((
+            format!("{}_16bit", name),
+            make_image(&[encode(pixels, 1), encode(pixels, 2)]),
+            ImageDescriptor::new(SYNTHETIC_ROWS, SYNTHETIC_COLUMNS, 1, 16),
+        ));
+    }
+    images
+}
+
+// returns the name, encoding and layout of the bundled test images followed
+// by the synthetic images
+fn images() -> Vec<(String, Vec<u8>, ImageDescriptor)> {
+    let mut images = Vec::new();
+    for (name, descriptor) in &[
+        ("ct", ImageDescriptor::new(512, 512, 1, 16)),
+        ("ct1", ImageDescriptor::new(512, 512, 1, 16)),
+        ("ct2", ImageDescriptor::new(512, 512, 1, 16)),
+        ("rf1", ImageDescriptor::new(512, 512, 1, 8)),
+        ("us1", ImageDescriptor::new(480, 640, 3, 8)),
+    ] {
+        let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+        images.push((name.to_string(), encoded, *descriptor));
+    }
+    images.extend(synthetic_images());
+    images
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let images = images();
+
+    let mut group = c.benchmark_group("decode");
+    for (name, encoded, descriptor) in &images {
+        let decoded_length = descriptor.decoded_length();
+        let mut decoded = vec![0; decoded_length];
+        group.throughput(Throughput::BytesDecimal(decoded_length as u64));
+        group.bench_function(name.as_str(), |b| {
+            b.iter(|| decode(encoded, &mut decoded).unwrap())
+        });
+    }
+    group.finish();
+
+    // decode_u16() only applies to the 16 bit images
+    let mut group = c.benchmark_group("decode_u16");
+    for (name, encoded, descriptor) in &images {
+        if encoded[0] != 2 {
+            continue;
+        }
+        let decoded_length = descriptor.decoded_length();
+        let mut decoded = vec![0; decoded_length / 2];
+        group.throughput(Throughput::BytesDecimal(decoded_length as u64));
+        group.bench_function(name.as_str(), |b| {
+            b.iter(|| decode_u16(encoded, &mut decoded).unwrap())
+        });
+    }
+    group.finish();
+
+    // allocating and zero filling a new buffer for each decode against
+    // decoding into the spare capacity of a new vector
+    let mut group = c.benchmark_group("decode_new_buffer");
+    for (name, encoded, descriptor) in &images {
+        let decoded_length = descriptor.decoded_length();
+        group.throughput(Throughput::BytesDecimal(decoded_length as u64));
+        group.bench_function(format!("{}/zeroed", name), |b| {
+            b.iter(|| {
+                let mut decoded = vec![0; decoded_length];
+                decode(encoded, &mut decoded).unwrap();
+                decoded
+            })
+        });
+        group.bench_function(format!("{}/decode_into_vec", name), |b| {
+            b.iter(|| {
+                let mut decoded = Vec::new();
+                decode_into_vec(encoded, &mut decoded, decoded_length).unwrap();
+                decoded
+            })
+        });
+    }
+    group.finish();
+}
+
+// encodes the decoded segments of each image with SegmentWriter one row at a
+// time, as an encoder of decoded pixels would, and maps the 8 bit images
+// through a lookup table with remap_frame(), which reencodes them without
+// decoding.  Throughput is in MB/s of decoded image like the decode groups.
+fn bench_encode(c: &mut Criterion) {
+    let images = images();
+
+    let mut group = c.benchmark_group("encode");
+    for (name, encoded, descriptor) in &images {
+        let planes: Vec<Vec<u8>> = get_segments(encoded)
+            .unwrap()
+            .iter()
+            .map(|segment| {
+                let mut plane = vec![0; descriptor.segment_length()];
+                decode_segment(segment, &mut plane, 1);
+                plane
+            })
+            .collect();
+        group.throughput(Throughput::BytesDecimal(descriptor.decoded_length() as u64));
+        group.bench_function(name.as_str(), |b| {
+            b.iter(|| {
+                planes
+                    .iter()
+                    .map(|plane| {
+                        let mut writer = SegmentWriter::new();
+                        for row in plane.chunks(descriptor.columns) {
+                            writer.push_literal(row);
+                            writer.end_row();
+                        }
+                        writer.finish()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+
+    // remap_frame() only applies to the 8 bit images, the table inverts the
+    // values so every run is rewritten
+    let mut lut = [0; 256];
+    for (index, value) in lut.iter_mut().enumerate() {
+        *value = 255 - index as u8;
+    }
+    let mut group = c.benchmark_group("remap");
+    for (name, encoded, descriptor) in &images {
+        if descriptor.bits_allocated != 8 {
+            continue;
+        }
+        group.throughput(Throughput::BytesDecimal(descriptor.decoded_length() as u64));
+        group.bench_function(name.as_str(), |b| {
+            b.iter(|| remap_frame(encoded, descriptor, &lut).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// compares decoding a whole image with decoding a 4x smaller thumbnail of it
+fn bench_thumbnail(c: &mut Criterion) {
+    let mut group = c.benchmark_group("thumbnail");
+    for (name, descriptor) in &[
+        ("ct", ImageDescriptor::new(512, 512, 1, 16)),
+        ("us1", ImageDescriptor::new(480, 640, 3, 8)),
+    ] {
+        let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+        let mut decoded = vec![0; descriptor.decoded_length()];
+        group.bench_function(format!("{}/decode", name), |b| {
+            b.iter(|| decode(&encoded, &mut decoded).unwrap())
+        });
+        for downsample in &[Downsample::Nearest, Downsample::Average] {
+            group.bench_function(format!("{}/{:?}", name, downsample), |b| {
+                b.iter(|| decode_thumbnail(&encoded, descriptor, 4, *downsample).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+// decoding straight into rows aligned to 256 bytes against decoding into a
+// packed buffer and copying each row
+fn bench_pitched(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pitched");
+    for (name, descriptor) in &[
+        ("ct", ImageDescriptor::new(512, 512, 1, 16)),
+        ("us1", ImageDescriptor::new(480, 640, 3, 8)),
+    ] {
+        let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+        let row_length = descriptor.columns * descriptor.segment_count();
+        let row_pitch = row_length.div_ceil(256) * 256;
+        let mut pitched = vec![0; pitched_length(descriptor, row_pitch, None).unwrap()];
+        let mut decoded = vec![0; descriptor.decoded_length()];
+        group.bench_function(format!("{}/decode_and_copy", name), |b| {
+            b.iter(|| {
+                decode(&encoded, &mut decoded).unwrap();
+                for (row, source) in decoded.chunks(row_length).enumerate() {
+                    let start = row * row_pitch;
+                    pitched[start..start + row_length].copy_from_slice(source);
+                }
+            })
+        });
+        group.bench_function(format!("{}/decode_pitched", name), |b| {
+            b.iter(|| decode_pitched(&encoded, descriptor, &mut pitched, row_pitch, None).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_decode,
+    bench_encode,
+    bench_thumbnail,
+    bench_pitched
+);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+// Compares the decode_segment() fast paths against the generic loop by
+// decoding every segment of the bundled test images
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use dicomrle::decode_segment::{decode_segment, decode_segment_generic, DecodeSegmentResult};
+use dicomrle::get_segments::get_segments;
+use std::fs;
+
+type DecodeSegment = fn(&[u8], &mut [u8], usize) -> DecodeSegmentResult;
+
+// decodes each segment of an image in turn, as decode() does
+fn decode_image(segments: &[&[u8]], decoded: &mut [u8], decode: DecodeSegment) {
+    let segment_count = segments.len();
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let start_index = if segment_count == 2 {
+            1 - segment_index
+        } else {
+            segment_index
+        };
+        decode(segment, &mut decoded[start_index..], segment_count);
+    }
+}
+
+fn bench_images(c: &mut Criterion) {
+    for (name, decoded_length) in &[
+        ("ct", 512 * 512 * 2),
+        ("rf1", 512 * 512),
+        ("us1", 640 * 480 * 3),
+    ] {
+        let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+        let segments = get_segments(&encoded).unwrap();
+        let mut decoded = vec![0; *decoded_length];
+
+        let mut group = c.benchmark_group(format!("decode_segment/{}", name));
+        group.throughput(Throughput::Bytes(*decoded_length as u64));
+        group.bench_function("fast", |b| {
+            b.iter(|| decode_image(&segments, &mut decoded, decode_segment))
+        });
+        group.bench_function("generic", |b| {
+            b.iter(|| decode_image(&segments, &mut decoded, decode_segment_generic))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_images);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+// Compares the decode strategies on 16 bit CT, 8 bit color ultrasound and a
+// synthetic 16 bit image the size of a mammogram
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use dicomrle::decode::{decode_with_strategy, DecodeStrategy};
+use std::fs;
+
+// encodes a 16 bit image where each row of each segment alternates between
+// literal and replicated runs of 8 bytes
+fn make_large_image(rows: usize, columns: usize) -> Vec<u8> {
+    let mut segments = vec![Vec::new(), Vec::new()];
+    for row in 0..rows {
+        for (segment_index, segment) in segments.iter_mut().enumerate() {
+            for column in (0..columns).step_by(16) {
+                segment.push(7);
+                for offset in 0..8 {
+                    segment.push((row + column + offset + segment_index) as u8);
+                }
+                segment.extend_from_slice(&[249, (row + segment_index) as u8]);
+            }
+        }
+    }
+
+    let mut header = [0u32; 16];
+    header[0] = 2;
+    header[1] = 64;
+    header[2] = 64 + segments[0].len() as u32;
+    let mut encoded: Vec<u8> = header
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    for segment in &segments {
+        encoded.extend_from_slice(segment);
+    }
+    encoded
+}
+
+fn bench_strategies(c: &mut Criterion) {
+    let mut images = Vec::new();
+    for (name, decoded_length, columns) in
+        &[("ct", 512 * 512 * 2, 512), ("us1", 640 * 480 * 3, 640)]
+    {
+        let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+        images.push((name.to_string(), encoded, *decoded_length, *columns));
+    }
+    images.push((
+        "mammogram".to_string(),
+        make_large_image(5120, 4096),
+        5120 * 4096 * 2,
+        4096,
+    ));
+
+    for (name, encoded, decoded_length, columns) in &images {
+        let mut decoded = vec![0; *decoded_length];
+
+        let mut group = c.benchmark_group(format!("decode_strategy/{}", name));
+        group.throughput(Throughput::Bytes(*decoded_length as u64));
+        if *decoded_length > 16 * 1024 * 1024 {
+            group.sample_size(20);
+        }
+        for (label, strategy) in &[
+            ("strided", DecodeStrategy::Strided),
+            ("planar", DecodeStrategy::Planar),
+            (
+                "row_blocked",
+                DecodeStrategy::RowBlocked { columns: *columns },
+            ),
+        ] {
+            group.bench_function(*label, |b| {
+                b.iter(|| decode_with_strategy(encoded, &mut decoded, *strategy).unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_strategies);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+// Generates the C header from src/ffi.rs into OUT_DIR when the ffi feature is
+// enabled.  A test in src/ffi.rs checks it against include/dicomrle.h, the
+// build never writes outside OUT_DIR so the crate can be packaged.
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("unable to read cbindgen.toml");
+    // only src/ffi.rs is parsed so public items elsewhere in the crate, such
+    // as constants, do not end up in the header
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", crate_dir))
+        .generate()
+        .expect("unable to generate the C header")
+        .write_to_file(format!("{}/dicomrle.h", out_dir));
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Command line arguments for a single subcommand
+pub struct Args {
+    /// arguments that are not options, in the order they were given
+    pub positional: Vec<String>,
+
+    /// options that do not take a value
+    flags: Vec<String>,
+
+    /// options that take a value
+    values: HashMap<String, String>,
+}
+
+impl Args {
+    /// Parses the arguments following the subcommand name
+    ///
+    /// # Arguments
+    ///
+    /// * `args`    - The arguments to parse
+    ///
+    /// * `flags`   - The names of the options that do not take a value
+    ///
+    /// * `options` - The names of the options that take a value
+    ///
+    pub fn parse(args: &[String], flags: &[&str], options: &[&str]) -> Result<Args, String> {
+        let mut result = Args {
+            positional: Vec::new(),
+            flags: Vec::new(),
+            values: HashMap::new(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if flags.contains(&name) {
+                    result.flags.push(name.to_owned());
+                } else if options.contains(&name) {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| format!("missing value for --{}", name))?;
+                    result.values.insert(name.to_owned(), value.clone());
+                } else {
+                    return Err(format!("unknown option --{}", name));
+                }
+            } else {
+                result.positional.push(arg.clone());
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Returns true if the flag was given
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// Returns the value of an option, if it was given
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    /// Returns the value of an option parsed as a number, if it was given
+    pub fn number<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.value(name) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value for --{}: {}", name, value)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_flags_options_and_positional() {
+        let args = Args::parse(
+            &strings(&["--json", "a.rle", "--rows", "512"]),
+            &["json"],
+            &["rows"],
+        )
+        .unwrap();
+        assert!(args.flag("json"));
+        assert_eq!(Some(512), args.number::<usize>("rows").unwrap());
+        assert_eq!(None, args.number::<usize>("columns").unwrap());
+        assert!(args.number::<u8>("rows").is_err());
+        assert_eq!(strings(&["a.rle"]), args.positional);
+    }
+
+    #[test]
+    fn rejects_unknown_option() {
+        assert!(Args::parse(&strings(&["--bogus"]), &[], &[]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_value() {
+        assert!(Args::parse(&strings(&["--rows"]), &[], &["rows"]).is_err());
+    }
+}
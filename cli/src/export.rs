@@ -0,0 +1,182 @@
+use crate::args::Args;
+use dicomrle::decode::decode_frame;
+use dicomrle::descriptor::ImageDescriptor;
+use dicomrle::export::{full_range_window, write_png, ExportOptions, Window};
+use dicomrle::part10::read_part10;
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+pub const USAGE: &str = "dicomrle export [--frame <number>] [--16bit] \
+                         [--window-center <center> --window-width <width>] \
+                         [--rescale-slope <slope>] [--rescale-intercept <intercept>] \
+                         [--signed] [--monochrome1] [--rows <rows> --columns <columns> \
+                         [--samples-per-pixel <samples>] [--bits-allocated <bits>]] \
+                         <input> <output.png>";
+
+/// Runs the export subcommand
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &["16bit", "signed", "monochrome1"],
+        &[
+            "frame",
+            "window-center",
+            "window-width",
+            "rescale-slope",
+            "rescale-intercept",
+            "rows",
+            "columns",
+            "samples-per-pixel",
+            "bits-allocated",
+        ],
+    )?;
+    if args.positional.len() != 2 {
+        return Err(format!("usage: {}", USAGE));
+    }
+    let input = &args.positional[0];
+    let output = &args.positional[1];
+
+    let bytes = fs::read(input).map_err(|error| format!("{}: {}", input, error))?;
+    let mut options = ExportOptions::default();
+    let mut window = None;
+
+    // Part 10 files carry their own image attributes, raw RLE files need them
+    // on the command line
+    let (encoded, descriptor) = if bytes.len() >= 132 && &bytes[128..132] == b"DICM" {
+        let part10 = read_part10(&bytes).map_err(|error| format!("{}: {}", input, error))?;
+        let frame = args.number::<usize>("frame")?.unwrap_or(1);
+        if frame == 0 || frame > part10.frames.len() {
+            return Err(format!(
+                "{}: frame {} does not exist, the file has {} frames",
+                input,
+                frame,
+                part10.frames.len()
+            ));
+        }
+
+        options.signed = part10.pixel_representation == 1;
+        options.monochrome1 = part10.photometric_interpretation.as_deref() == Some("MONOCHROME1");
+        options.rescale_slope = part10.rescale_slope.unwrap_or(1.0);
+        options.rescale_intercept = part10.rescale_intercept.unwrap_or(0.0);
+        if let (Some(center), Some(width)) = (part10.window_center, part10.window_width) {
+            window = Some(Window { center, width });
+        }
+
+        (part10.frames[frame - 1], part10.descriptor)
+    } else {
+        let rows = args.number("rows")?;
+        let columns = args.number("columns")?;
+        let descriptor = match (rows, columns) {
+            (Some(rows), Some(columns)) => ImageDescriptor::new(
+                rows,
+                columns,
+                args.number("samples-per-pixel")?.unwrap_or(1),
+                args.number("bits-allocated")?.unwrap_or(8),
+            ),
+            _ => return Err("--rows and --columns are required for raw RLE files".to_owned()),
+        };
+        (&bytes[..], descriptor)
+    };
+
+    if args.flag("signed") {
+        options.signed = true;
+    }
+    if args.flag("monochrome1") {
+        options.monochrome1 = true;
+    }
+    if let Some(slope) = args.number("rescale-slope")? {
+        options.rescale_slope = slope;
+    }
+    if let Some(intercept) = args.number("rescale-intercept")? {
+        options.rescale_intercept = intercept;
+    }
+    match (args.number("window-center")?, args.number("window-width")?) {
+        (Some(center), Some(width)) => window = Some(Window { center, width }),
+        (None, None) => {}
+        _ => return Err("--window-center and --window-width must be given together".to_owned()),
+    }
+
+    let (decoded, diagnostics) =
+        decode_frame(encoded, &descriptor).map_err(|error| format!("{}: {}", input, error))?;
+    if diagnostics.incomplete_decode {
+        eprintln!("{}: warning: incomplete decode", input);
+    }
+
+    // 16 bit data is windowed down to 8 bits unless 16 bit output is requested
+    options.window = if args.flag("16bit") {
+        None
+    } else if window.is_none() && descriptor.bits_allocated == 16 {
+        Some(full_range_window(&decoded, &descriptor, &options))
+    } else {
+        window
+    };
+
+    let file = File::create(output).map_err(|error| format!("{}: {}", output, error))?;
+    write_png(BufWriter::new(file), &decoded, &descriptor, &options)
+        .map_err(|error| format!("{}: {}", output, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use std::fs::{self, File};
+
+    // exports a bundled image and returns the decoded PNG along with its info
+    fn export(name: &str, options: &[&str]) -> (Vec<u8>, png::OutputInfo) {
+        let output = std::env::temp_dir().join(format!(
+            "dicomrle-export-{}-{}.png",
+            name,
+            std::process::id()
+        ));
+        let input = format!("../tests/rleimage/{}.rle", name);
+        let mut args: Vec<String> = options.iter().map(|option| option.to_string()).collect();
+        args.push(input);
+        args.push(output.to_str().unwrap().to_owned());
+        run(&args).unwrap();
+
+        let mut reader = png::Decoder::new(File::open(&output).unwrap())
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        fs::remove_file(&output).unwrap();
+        (pixels, info)
+    }
+
+    #[test]
+    fn exports_color_image() {
+        let (pixels, info) = export(
+            "us1",
+            &[
+                "--rows",
+                "480",
+                "--columns",
+                "640",
+                "--samples-per-pixel",
+                "3",
+            ],
+        );
+        assert_eq!((640, 480), (info.width, info.height));
+        assert_eq!(png::ColorType::Rgb, info.color_type);
+        assert_eq!(fs::read("../tests/rawimage/us1.raw").unwrap(), pixels);
+    }
+
+    #[test]
+    fn exports_sixteen_bit_image() {
+        let options = [
+            "--rows",
+            "512",
+            "--columns",
+            "512",
+            "--bits-allocated",
+            "16",
+        ];
+        let (_, info) = export("ct", &options);
+        assert_eq!((512, 512), (info.width, info.height));
+        assert_eq!(png::BitDepth::Eight, info.bit_depth);
+
+        let (_, info) = export("ct", &[&options[..], &["--16bit"]].concat());
+        assert_eq!(png::ColorType::Grayscale, info.color_type);
+        assert_eq!(png::BitDepth::Sixteen, info.bit_depth);
+    }
+}
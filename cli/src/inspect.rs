@@ -0,0 +1,235 @@
+use crate::args::Args;
+use crate::json::Json;
+use dicomrle::decode_segment::DecodeSegmentResult;
+use dicomrle::inspect::{inspect, Inspection, SegmentInspection};
+use std::fmt::Write;
+
+pub const USAGE: &str = "dicomrle inspect [--json] [--rows <rows> --columns <columns>] <file>";
+
+// returns the names of the diagnostic flags that are set
+pub fn flag_names(result: &DecodeSegmentResult) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if result.literal_run_underflow {
+        names.push("literal_run_underflow");
+    }
+    if result.replicated_run_underflow {
+        names.push("replicated_run_underflow");
+    }
+    if result.decoded_overflow {
+        names.push("decoded_overflow");
+    }
+    if result.invalid_prefix {
+        names.push("invalid_prefix");
+    }
+    names
+}
+
+// formats a run length histogram as "length:count" pairs, skipping empty bins
+fn histogram_text(histogram: &[usize; 128]) -> String {
+    let mut text = String::new();
+    for (index, count) in histogram.iter().enumerate() {
+        if *count > 0 {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            write!(text, "{}:{}", index + 1, count).unwrap();
+        }
+    }
+    if text.is_empty() {
+        text.push_str("none");
+    }
+    text
+}
+
+// formats a run length histogram as a JSON object keyed by run length
+fn histogram_json(histogram: &[usize; 128]) -> Json {
+    let mut json = Json::object();
+    for (index, count) in histogram.iter().enumerate() {
+        if *count > 0 {
+            json = json.with(&(index + 1).to_string(), (*count).into());
+        }
+    }
+    json
+}
+
+fn segment_text(index: usize, segment: &SegmentInspection) -> String {
+    let result = &segment.decode_result;
+    let runs = &segment.runs;
+    let flags = flag_names(result);
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "segment {}: offset {}, length {}",
+        index + 1,
+        segment.offset,
+        segment.length
+    )
+    .unwrap();
+    writeln!(
+        text,
+        "  decoded {} bytes, stopped at {} of {}, compression ratio {:.2}",
+        result.bytes_decoded,
+        result.bytes_consumed,
+        segment.length,
+        segment.compression_ratio()
+    )
+    .unwrap();
+    writeln!(
+        text,
+        "  literal runs: {} ({} bytes), replicated runs: {} ({} bytes)",
+        runs.literal_runs, runs.literal_bytes, runs.replicated_runs, runs.replicated_bytes
+    )
+    .unwrap();
+    writeln!(
+        text,
+        "  literal run lengths: {}",
+        histogram_text(&runs.literal_run_lengths)
+    )
+    .unwrap();
+    writeln!(
+        text,
+        "  replicated run lengths: {}",
+        histogram_text(&runs.replicated_run_lengths)
+    )
+    .unwrap();
+    if flags.is_empty() {
+        writeln!(text, "  flags: none").unwrap();
+    } else {
+        writeln!(text, "  flags: {}", flags.join(", ")).unwrap();
+    }
+    text
+}
+
+/// Formats an inspection as human readable text
+pub fn to_text(inspection: &Inspection) -> String {
+    let offsets: Vec<String> = inspection
+        .segment_offsets
+        .iter()
+        .map(|offset| offset.to_string())
+        .collect();
+
+    let mut text = String::new();
+    writeln!(text, "encoded length: {}", inspection.encoded_length).unwrap();
+    writeln!(text, "segment count: {}", inspection.segments.len()).unwrap();
+    writeln!(text, "segment offsets: {}", offsets.join(", ")).unwrap();
+    for (index, segment) in inspection.segments.iter().enumerate() {
+        text.push_str(&segment_text(index, segment));
+    }
+    text
+}
+
+/// Formats an inspection as JSON
+pub fn to_json(inspection: &Inspection) -> Json {
+    let segments = inspection
+        .segments
+        .iter()
+        .map(|segment| {
+            let result = &segment.decode_result;
+            let runs = &segment.runs;
+            Json::object()
+                .with("offset", segment.offset.into())
+                .with("length", segment.length.into())
+                .with("bytes_decoded", result.bytes_decoded.into())
+                .with("bytes_consumed", result.bytes_consumed.into())
+                .with("compression_ratio", segment.compression_ratio().into())
+                .with("literal_runs", runs.literal_runs.into())
+                .with("literal_bytes", runs.literal_bytes.into())
+                .with("replicated_runs", runs.replicated_runs.into())
+                .with("replicated_bytes", runs.replicated_bytes.into())
+                .with(
+                    "literal_run_lengths",
+                    histogram_json(&runs.literal_run_lengths),
+                )
+                .with(
+                    "replicated_run_lengths",
+                    histogram_json(&runs.replicated_run_lengths),
+                )
+                .with("literal_run_underflow", result.literal_run_underflow.into())
+                .with(
+                    "replicated_run_underflow",
+                    result.replicated_run_underflow.into(),
+                )
+                .with("decoded_overflow", result.decoded_overflow.into())
+                .with("invalid_prefix", result.invalid_prefix.into())
+        })
+        .collect();
+
+    Json::object()
+        .with("encoded_length", inspection.encoded_length.into())
+        .with("segment_count", inspection.segments.len().into())
+        .with("segment_offsets", inspection.segment_offsets.clone().into())
+        .with("segments", Json::Array(segments))
+}
+
+/// Runs the inspect subcommand
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["json"], &["rows", "columns"])?;
+    if args.positional.len() != 1 {
+        return Err(format!("usage: {}", USAGE));
+    }
+
+    let segment_length = match (
+        args.number::<usize>("rows")?,
+        args.number::<usize>("columns")?,
+    ) {
+        (Some(rows), Some(columns)) => Some(
+            rows.checked_mul(columns)
+                .ok_or_else(|| format!("{} rows of {} columns is too large", rows, columns))?,
+        ),
+        (None, None) => None,
+        _ => return Err("--rows and --columns must be given together".to_owned()),
+    };
+
+    let path = &args.positional[0];
+    let encoded = std::fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+    let inspection =
+        inspect(&encoded, segment_length).map_err(|error| format!("{}: {}", path, error))?;
+
+    if args.flag("json") {
+        println!("{}", to_json(&inspection));
+    } else {
+        print!("{}", to_text(&inspection));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, to_json, to_text};
+    use dicomrle::inspect::inspect;
+
+    fn inspection() -> dicomrle::inspect::Inspection {
+        let encoded = std::fs::read("../tests/rleimage/rf1.rle").unwrap();
+        inspect(&encoded, Some(512 * 512)).unwrap()
+    }
+
+    #[test]
+    fn text_lists_each_segment() {
+        let text = to_text(&inspection());
+        assert!(text.contains("segment count: 1\n"));
+        assert!(text.contains("segment offsets: 64\n"));
+        assert!(text.contains("segment 1: offset 64"));
+        // rf1 ends with a zero pad byte
+        assert!(text.contains("stopped at 170133 of 170134"));
+        assert!(text.contains("flags: literal_run_underflow"));
+    }
+
+    #[test]
+    fn json_includes_histograms() {
+        let json = to_json(&inspection()).to_string();
+        assert!(json.starts_with("{\"encoded_length\":170198,\"segment_count\":1,"));
+        assert!(json.contains("\"bytes_decoded\":262144"));
+        assert!(json.contains("\"literal_run_lengths\":{\"1\":"));
+    }
+
+    #[test]
+    fn rejects_overflowing_image_size() {
+        let args: Vec<String> = ["--rows", &usize::MAX.to_string(), "--columns", "2", "a.rle"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert!(run(&args).unwrap_err().contains("too large"));
+    }
+}
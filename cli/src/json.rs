@@ -0,0 +1,145 @@
+use std::fmt;
+
+/// A JSON value.  Only what the reports need is supported.
+pub enum Json {
+    Null,
+    Bool(bool),
+    Integer(u64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Creates an empty JSON object
+    pub fn object() -> Json {
+        Json::Object(Vec::new())
+    }
+
+    /// Adds a member to a JSON object and returns it.  Does nothing if this is
+    /// not an object.
+    pub fn with(mut self, key: &str, value: Json) -> Json {
+        if let Json::Object(ref mut members) = self {
+            members.push((key.to_owned(), value));
+        }
+        self
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map(Into::into).unwrap_or(Json::Null)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Integer(value as u64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Json {
+        Json::Float(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Json {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Integer(value) => write!(f, "{}", value),
+            // JSON has no representation for NaN or infinity
+            Json::Float(value) if !value.is_finite() => f.write_str("null"),
+            Json::Float(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(members) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    #[test]
+    fn serializes_nested_values() {
+        let json = Json::object()
+            .with("name", "a\"b\n".into())
+            .with("values", vec![1usize, 2].into())
+            .with("ratio", 2.5.into())
+            .with("ok", true.into())
+            .with("none", None::<bool>.into());
+        assert_eq!(
+            r#"{"name":"a\"b\n","values":[1,2],"ratio":2.5,"ok":true,"none":null}"#,
+            json.to_string()
+        );
+    }
+
+    #[test]
+    fn non_finite_floats_are_null() {
+        assert_eq!("null", Json::Float(f64::NAN).to_string());
+    }
+}
@@ -0,0 +1,32 @@
+mod args;
+mod export;
+mod inspect;
+mod json;
+mod validate;
+
+use std::process;
+
+fn usage() -> String {
+    format!(
+        "usage:\n    {}\n    {}\n    {}",
+        inspect::USAGE,
+        validate::USAGE,
+        export::USAGE
+    )
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(|command| command.as_str()) {
+        Some("inspect") => inspect::run(&args[1..]),
+        Some("validate") => validate::run(&args[1..]),
+        Some("export") => export::run(&args[1..]),
+        _ => Err(usage()),
+    };
+
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
+}
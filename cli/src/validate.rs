@@ -0,0 +1,621 @@
+use crate::args::Args;
+use crate::inspect::flag_names;
+use crate::json::Json;
+use dicomrle::error::Error;
+use dicomrle::inspect::inspect;
+use dicomrle::limits::DecodeLimits;
+use dicomrle::part10::read_part10_with_limits;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+pub const USAGE: &str = "dicomrle validate [--json] [--threads <count>] [--output <file>] \
+                         [--rows <rows> --columns <columns>] <directory>";
+
+/// The result of validating a single frame
+pub struct FrameReport {
+    /// the error variant and message if the frame could not be decoded
+    pub error: Option<(&'static str, String)>,
+
+    /// true if any segment did not decode to the expected length
+    pub incomplete: bool,
+
+    /// the diagnostic flags set by any segment
+    pub flags: Vec<&'static str>,
+}
+
+impl FrameReport {
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none() && !self.incomplete
+    }
+}
+
+/// The result of validating a single file
+pub struct FileReport {
+    pub path: PathBuf,
+
+    /// the manufacturer from the Part 10 header, if any
+    pub manufacturer: Option<String>,
+
+    /// the error variant and message if the file could not be read
+    pub error: Option<(&'static str, String)>,
+
+    pub frames: Vec<FrameReport>,
+}
+
+fn variant(error: &Error) -> &'static str {
+    match error {
+        Error::Format(_) => "Format",
+        Error::Part10(_) => "Part10",
+        Error::Export(_) => "Export",
+        Error::LimitExceeded { .. } => "LimitExceeded",
+    }
+}
+
+fn to_report_error(error: Error) -> Option<(&'static str, String)> {
+    Some((variant(&error), error.to_string()))
+}
+
+/// Validates a single RLE frame.  If the segment length is not known, all
+/// segments are expected to decode to the same length.
+pub fn validate_frame(
+    encoded: &[u8],
+    segment_length: Option<usize>,
+    segment_count: Option<usize>,
+) -> FrameReport {
+    let mut report = FrameReport {
+        error: None,
+        incomplete: false,
+        flags: Vec::new(),
+    };
+
+    let inspection = match inspect(encoded, segment_length) {
+        Ok(inspection) => inspection,
+        Err(error) => {
+            report.error = to_report_error(error);
+            return report;
+        }
+    };
+
+    if let Some(segment_count) = segment_count {
+        if inspection.segments.len() != segment_count {
+            report.error = to_report_error(Error::Format(format!(
+                "expected {} segments but found {}",
+                segment_count,
+                inspection.segments.len()
+            )));
+            return report;
+        }
+    }
+
+    let expected_length =
+        segment_length.unwrap_or(inspection.segments[0].decode_result.bytes_decoded);
+
+    for segment in &inspection.segments {
+        if segment.decode_result.bytes_decoded != expected_length {
+            report.incomplete = true;
+        }
+        for flag in flag_names(&segment.decode_result) {
+            if !report.flags.contains(&flag) {
+                report.flags.push(flag);
+            }
+        }
+    }
+
+    report
+}
+
+fn is_rle(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.eq_ignore_ascii_case("rle"))
+        .unwrap_or(false)
+}
+
+// returns true if the file starts with the 128 byte preamble and DICM prefix
+// of a DICOM Part 10 file, reading only those 132 bytes
+fn has_part10_prefix(path: &Path) -> io::Result<bool> {
+    let mut prefix = [0; 132];
+    match File::open(path)?.read_exact(&mut prefix) {
+        Ok(()) => Ok(&prefix[128..] == b"DICM"),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Validates every frame in a file.  The segment length is only used for raw
+/// RLE images since Part 10 files carry their own image attributes, which are
+/// checked against the default `DecodeLimits`.  Returns None if the file is
+/// neither a raw RLE image nor a DICOM Part 10 file, in which case only the
+/// first 132 bytes of the file are read.
+pub fn validate_file(path: &Path, segment_length: Option<usize>) -> Option<FileReport> {
+    let mut report = FileReport {
+        path: path.to_owned(),
+        manufacturer: None,
+        error: None,
+        frames: Vec::new(),
+    };
+
+    // only files that will be validated are read in full
+    let is_rle = is_rle(path);
+    let bytes = if is_rle {
+        fs::read(path)
+    } else {
+        match has_part10_prefix(path) {
+            Ok(true) => fs::read(path),
+            Ok(false) => return None,
+            Err(error) => Err(error),
+        }
+    };
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            report.error = Some(("Io", error.to_string()));
+            return Some(report);
+        }
+    };
+
+    if is_rle {
+        report
+            .frames
+            .push(validate_frame(&bytes, segment_length, None));
+    } else {
+        match read_part10_with_limits(&bytes, &DecodeLimits::default()) {
+            Ok(part10) => {
+                report.manufacturer = part10.manufacturer.clone();
+                let descriptor = &part10.descriptor;
+                for frame in &part10.frames {
+                    report.frames.push(validate_frame(
+                        frame,
+                        Some(descriptor.segment_length()),
+                        Some(descriptor.segment_count()),
+                    ));
+                }
+            }
+            Err(error) => report.error = to_report_error(error),
+        }
+    }
+
+    Some(report)
+}
+
+// symbolic links to files are followed but links to directories are not, so
+// a link back up the tree cannot make this recurse forever
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_files(&path, files)?;
+        } else if !file_type.is_symlink() || path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Validates every file below a directory using the given number of threads.
+/// Returns the reports sorted by path along with the number of files skipped.
+pub fn validate_directory(
+    directory: &Path,
+    segment_length: Option<usize>,
+    threads: usize,
+) -> io::Result<(Vec<FileReport>, usize)> {
+    let mut files = Vec::new();
+    collect_files(directory, &mut files)?;
+    files.sort();
+
+    let next = AtomicUsize::new(0);
+    let reports = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= files.len() {
+                    break;
+                }
+                if let Some(report) = validate_file(&files[index], segment_length) {
+                    reports.lock().unwrap().push(report);
+                }
+            });
+        }
+    });
+
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_by(|a, b| a.path.cmp(&b.path));
+    let skipped = files.len() - reports.len();
+    Ok((reports, skipped))
+}
+
+/// Aggregate counts over all validated files
+#[derive(Default)]
+pub struct Summary {
+    pub files: usize,
+    pub skipped: usize,
+    pub frames: usize,
+    pub valid_frames: usize,
+    pub incomplete_frames: usize,
+    pub errors: BTreeMap<&'static str, usize>,
+    pub flags: BTreeMap<&'static str, usize>,
+
+    /// the number of frames and the number of invalid frames per manufacturer
+    pub manufacturers: BTreeMap<String, (usize, usize)>,
+}
+
+impl Summary {
+    pub fn new(reports: &[FileReport], skipped: usize) -> Summary {
+        let mut summary = Summary {
+            files: reports.len(),
+            skipped,
+            ..Default::default()
+        };
+
+        for report in reports {
+            let manufacturer = report
+                .manufacturer
+                .clone()
+                .unwrap_or_else(|| "unknown".to_owned());
+            let counts = summary.manufacturers.entry(manufacturer).or_default();
+
+            if let Some((variant, _)) = report.error {
+                *summary.errors.entry(variant).or_default() += 1;
+            }
+
+            for frame in &report.frames {
+                summary.frames += 1;
+                counts.0 += 1;
+                if frame.is_valid() {
+                    summary.valid_frames += 1;
+                } else {
+                    counts.1 += 1;
+                }
+                if frame.incomplete {
+                    summary.incomplete_frames += 1;
+                }
+                if let Some((variant, _)) = frame.error {
+                    *summary.errors.entry(variant).or_default() += 1;
+                }
+                for flag in &frame.flags {
+                    *summary.flags.entry(flag).or_default() += 1;
+                }
+            }
+        }
+
+        summary
+    }
+}
+
+fn frame_text(frame: &FrameReport) -> String {
+    let mut text = match &frame.error {
+        Some((variant, message)) => format!("error {}: {}", variant, message),
+        None if frame.incomplete => "incomplete".to_owned(),
+        None => "ok".to_owned(),
+    };
+    if !frame.flags.is_empty() {
+        write!(text, "; flags: {}", frame.flags.join(", ")).unwrap();
+    }
+    text
+}
+
+fn counts_text<K: std::fmt::Display>(counts: &BTreeMap<K, usize>) -> String {
+    if counts.is_empty() {
+        return "none".to_owned();
+    }
+    let counts: Vec<String> = counts
+        .iter()
+        .map(|(key, count)| format!("{}: {}", key, count))
+        .collect();
+    counts.join(", ")
+}
+
+/// Formats the reports and summary as human readable text
+pub fn to_text(reports: &[FileReport], summary: &Summary) -> String {
+    let mut text = String::new();
+    for report in reports {
+        let path = report.path.display();
+        if let Some((variant, message)) = &report.error {
+            writeln!(text, "{}: error {}: {}", path, variant, message).unwrap();
+        }
+        for (index, frame) in report.frames.iter().enumerate() {
+            writeln!(text, "{} frame {}: {}", path, index + 1, frame_text(frame)).unwrap();
+        }
+    }
+
+    writeln!(text, "summary").unwrap();
+    writeln!(
+        text,
+        "  files: {} ({} skipped)",
+        summary.files, summary.skipped
+    )
+    .unwrap();
+    writeln!(text, "  frames: {}", summary.frames).unwrap();
+    writeln!(text, "  valid frames: {}", summary.valid_frames).unwrap();
+    writeln!(text, "  incomplete frames: {}", summary.incomplete_frames).unwrap();
+    writeln!(text, "  errors: {}", counts_text(&summary.errors)).unwrap();
+    writeln!(text, "  flags: {}", counts_text(&summary.flags)).unwrap();
+    writeln!(text, "  manufacturers:").unwrap();
+    for (manufacturer, (frames, invalid)) in &summary.manufacturers {
+        writeln!(
+            text,
+            "    {}: {} frames, {} invalid",
+            manufacturer, frames, invalid
+        )
+        .unwrap();
+    }
+    text
+}
+
+fn error_json(error: &Option<(&'static str, String)>) -> Json {
+    match error {
+        Some((variant, message)) => Json::object()
+            .with("variant", (*variant).into())
+            .with("message", message.as_str().into()),
+        None => Json::Null,
+    }
+}
+
+fn counts_json<K: ToString>(counts: &BTreeMap<K, usize>) -> Json {
+    let mut json = Json::object();
+    for (key, count) in counts {
+        json = json.with(&key.to_string(), (*count).into());
+    }
+    json
+}
+
+/// Formats the reports and summary as JSON
+pub fn to_json(reports: &[FileReport], summary: &Summary) -> Json {
+    let files = reports
+        .iter()
+        .map(|report| {
+            let frames = report
+                .frames
+                .iter()
+                .map(|frame| {
+                    Json::object()
+                        .with("valid", frame.is_valid().into())
+                        .with("incomplete", frame.incomplete.into())
+                        .with("error", error_json(&frame.error))
+                        .with("flags", frame.flags.clone().into())
+                })
+                .collect();
+            Json::object()
+                .with("path", report.path.display().to_string().into())
+                .with("manufacturer", report.manufacturer.clone().into())
+                .with("error", error_json(&report.error))
+                .with("frames", Json::Array(frames))
+        })
+        .collect();
+
+    let mut manufacturers = Json::object();
+    for (manufacturer, (frames, invalid)) in &summary.manufacturers {
+        manufacturers = manufacturers.with(
+            manufacturer,
+            Json::object()
+                .with("frames", (*frames).into())
+                .with("invalid_frames", (*invalid).into()),
+        );
+    }
+
+    let summary = Json::object()
+        .with("files", summary.files.into())
+        .with("skipped", summary.skipped.into())
+        .with("frames", summary.frames.into())
+        .with("valid_frames", summary.valid_frames.into())
+        .with("incomplete_frames", summary.incomplete_frames.into())
+        .with("errors", counts_json(&summary.errors))
+        .with("flags", counts_json(&summary.flags))
+        .with("manufacturers", manufacturers);
+
+    Json::object()
+        .with("files", Json::Array(files))
+        .with("summary", summary)
+}
+
+/// Runs the validate subcommand
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["json"], &["threads", "output", "rows", "columns"])?;
+    if args.positional.len() != 1 {
+        return Err(format!("usage: {}", USAGE));
+    }
+
+    // raw RLE files carry no image attributes, so they can be given here
+    let segment_length = match (
+        args.number::<usize>("rows")?,
+        args.number::<usize>("columns")?,
+    ) {
+        (Some(rows), Some(columns)) => Some(
+            rows.checked_mul(columns)
+                .ok_or_else(|| format!("{} rows of {} columns is too large", rows, columns))?,
+        ),
+        (None, None) => None,
+        _ => return Err("--rows and --columns must be given together".to_owned()),
+    };
+    let threads = match args.number("threads")? {
+        Some(threads) => threads,
+        None => thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1),
+    };
+
+    let directory = Path::new(&args.positional[0]);
+    let (reports, skipped) = validate_directory(directory, segment_length, threads)
+        .map_err(|error| format!("{}: {}", directory.display(), error))?;
+    let summary = Summary::new(&reports, skipped);
+
+    let output = if args.flag("json") {
+        format!("{}\n", to_json(&reports, &summary))
+    } else {
+        to_text(&reports, &summary)
+    };
+
+    match args.value("output") {
+        Some(path) => fs::write(path, output).map_err(|error| format!("{}: {}", path, error)),
+        None => {
+            print!("{}", output);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        has_part10_prefix, run, to_text, validate_directory, validate_file, validate_frame, Summary,
+    };
+    use std::fs;
+
+    fn read(name: &str) -> Vec<u8> {
+        fs::read(format!("../tests/rleimage/{}.rle", name)).unwrap()
+    }
+
+    // an explicit VR little endian US element of the Image Pixel module
+    fn image_pixel_element(element: u16, value: u16) -> Vec<u8> {
+        let mut bytes = vec![0x28, 0x00];
+        bytes.extend_from_slice(&element.to_le_bytes());
+        bytes.extend_from_slice(b"US");
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    // makes an RLE Lossless Part 10 file with one 8 bit grayscale frame
+    fn make_part10(rows: u16, columns: u16, frame: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes.extend_from_slice(b"DICM");
+        let transfer_syntax = b"1.2.840.10008.1.2.5\0";
+        bytes.extend_from_slice(&[0x02, 0x00, 0x10, 0x00, b'U', b'I']);
+        bytes.extend_from_slice(&(transfer_syntax.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(transfer_syntax);
+        bytes.extend(image_pixel_element(0x0002, 1));
+        bytes.extend(image_pixel_element(0x0010, rows));
+        bytes.extend(image_pixel_element(0x0011, columns));
+        bytes.extend(image_pixel_element(0x0100, 8));
+        // encapsulated pixel data with an empty offset table and one fragment
+        bytes.extend_from_slice(&[0xe0, 0x7f, 0x10, 0x00, b'O', b'B', 0, 0]);
+        bytes.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+        bytes.extend_from_slice(&[0xfe, 0xff, 0x00, 0xe0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0xfe, 0xff, 0x00, 0xe0]);
+        bytes.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(frame);
+        bytes.extend_from_slice(&[0xfe, 0xff, 0xdd, 0xe0, 0, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn valid_frame() {
+        let report = validate_frame(&read("ct"), Some(512 * 512), Some(2));
+        assert!(report.is_valid());
+        assert!(report.flags.is_empty());
+    }
+
+    #[test]
+    fn truncated_frame_is_incomplete() {
+        let mut encoded = read("us1");
+        encoded.truncate(encoded.len() - 1000);
+        let report = validate_frame(&encoded, None, None);
+        assert!(!report.is_valid());
+        assert!(report.incomplete);
+    }
+
+    #[test]
+    fn segment_count_mismatch_is_format_error() {
+        let report = validate_frame(&read("ct"), Some(512 * 512), Some(1));
+        assert_eq!("Format", report.error.unwrap().0);
+    }
+
+    #[test]
+    fn directory_summary() {
+        let directory =
+            std::env::temp_dir().join(format!("dicomrle-validate-{}", std::process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("rf1.rle"), read("rf1")).unwrap();
+        fs::write(directory.join("nested/bad.dcm"), b"not dicom").unwrap();
+        let mut truncated = vec![0; 128];
+        truncated.extend_from_slice(b"DICM");
+        fs::write(directory.join("nested/truncated.dcm"), truncated).unwrap();
+
+        let (reports, skipped) = validate_directory(&directory, None, 2).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let summary = Summary::new(&reports, skipped);
+        assert_eq!(2, summary.files);
+        assert_eq!(1, summary.skipped);
+        assert_eq!(1, summary.frames);
+        assert_eq!(1, summary.valid_frames);
+        assert_eq!(Some(&1), summary.errors.get("Part10"));
+        assert_eq!(Some(&1), summary.flags.get("literal_run_underflow"));
+
+        let text = to_text(&reports, &summary);
+        assert!(text.contains("rf1.rle frame 1: ok; flags: literal_run_underflow\n"));
+        assert!(text.contains("truncated.dcm: error Part10:"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_symlink_cycle_is_not_followed() {
+        let directory =
+            std::env::temp_dir().join(format!("dicomrle-symlink-{}", std::process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("rf1.rle"), read("rf1")).unwrap();
+        std::os::unix::fs::symlink(&directory, directory.join("nested/parent")).unwrap();
+        std::os::unix::fs::symlink(directory.join("rf1.rle"), directory.join("link.rle")).unwrap();
+
+        let (reports, _) = validate_directory(&directory, None, 1).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(2, reports.len());
+        assert!(reports.iter().all(|report| report.frames.len() == 1));
+    }
+
+    #[test]
+    fn rejects_overflowing_image_size() {
+        let args: Vec<String> = ["--rows", &usize::MAX.to_string(), "--columns", "2", "."]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert!(run(&args).unwrap_err().contains("too large"));
+    }
+
+    #[test]
+    fn part10_file() {
+        let path = std::env::temp_dir().join(format!("dicomrle-part10-{}.dcm", std::process::id()));
+        fs::write(&path, make_part10(512, 512, &read("rf1"))).unwrap();
+        let report = validate_file(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(report.error.is_none());
+        assert_eq!(1, report.frames.len());
+        assert!(report.frames[0].error.is_none());
+    }
+
+    #[test]
+    fn oversized_part10_image_is_a_limit_error() {
+        let path =
+            std::env::temp_dir().join(format!("dicomrle-oversized-{}.dcm", std::process::id()));
+        fs::write(&path, make_part10(65535, 65535, &read("rf1"))).unwrap();
+        let report = validate_file(&path, None).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!("LimitExceeded", report.error.unwrap().0);
+        assert!(report.frames.is_empty());
+    }
+
+    #[test]
+    fn part10_prefix() {
+        let path = std::env::temp_dir().join(format!("dicomrle-prefix-{}", std::process::id()));
+        fs::write(&path, b"DICM").unwrap();
+        assert!(!has_part10_prefix(&path).unwrap());
+        fs::write(&path, vec![0; 4096]).unwrap();
+        assert!(!has_part10_prefix(&path).unwrap());
+        fs::write(&path, make_part10(512, 512, &read("rf1"))).unwrap();
+        assert!(has_part10_prefix(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        assert!(has_part10_prefix(&path).is_err());
+    }
+}
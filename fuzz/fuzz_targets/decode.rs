@@ -0,0 +1,50 @@
+// Decodes arbitrary input with every decode entry point and checks that they
+// agree.  The first three bytes choose the decoded length and the number of
+// columns for the row blocked strategy, the rest is the encoded image.
+#![no_main]
+use dicomrle::decode::{
+    decode, decode_parallel, decode_u16, decode_uninit, decode_with_strategy, DecodeStrategy,
+};
+use libfuzzer_sys::fuzz_target;
+use std::mem::MaybeUninit;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let decoded_length = u16::from_le_bytes([data[0], data[1]]) as usize;
+    let columns = data[2] as usize;
+    let encoded = &data[3..];
+
+    let mut decoded = vec![0; decoded_length];
+    let result = decode(encoded, &mut decoded);
+
+    for strategy in &[
+        DecodeStrategy::Strided,
+        DecodeStrategy::Planar,
+        DecodeStrategy::RowBlocked { columns },
+    ] {
+        let mut strategy_decoded = vec![0; decoded_length];
+        let strategy_result = decode_with_strategy(encoded, &mut strategy_decoded, *strategy);
+        assert_eq!(result.is_ok(), strategy_result.is_ok());
+        if let (Ok(expected), Ok(actual)) = (&result, &strategy_result) {
+            assert_eq!(expected, actual);
+            assert!(decoded == strategy_decoded);
+        }
+    }
+
+    let mut parallel_decoded = vec![0; decoded_length];
+    let parallel_result = decode_parallel(encoded, &mut parallel_decoded);
+    assert_eq!(result.is_ok(), parallel_result.is_ok());
+    assert!(decoded == parallel_decoded);
+
+    let mut uninit_decoded = vec![MaybeUninit::uninit(); decoded_length];
+    let uninit_result = decode_uninit(encoded, &mut uninit_decoded);
+    assert_eq!(result.is_ok(), uninit_result.is_ok());
+    if let Ok((initialized, _)) = uninit_result {
+        assert!(decoded[..initialized.len()] == *initialized);
+    }
+
+    let mut decoded_u16 = vec![0; decoded_length / 2];
+    let _ = decode_u16(encoded, &mut decoded_u16);
+});
@@ -0,0 +1,48 @@
+// Decodes arbitrary input into a pitched buffer and checks each row matches
+// the same row of the packed decoded image.  The first four bytes choose the
+// image layout and the padding, the rest is the encoded image.
+#![no_main]
+use dicomrle::decode::decode_frame;
+use dicomrle::descriptor::ImageDescriptor;
+use dicomrle::pitched::{decode_pitched, pitched_length};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+    let rows = data[0] as usize % 32 + 1;
+    let columns = data[1] as usize % 32 + 1;
+    let (samples_per_pixel, bits_allocated) = match data[2] % 3 {
+        0 => (1, 8),
+        1 => (1, 16),
+        _ => (3, 8),
+    };
+    let descriptor = ImageDescriptor::new(rows, columns, samples_per_pixel, bits_allocated);
+    let pixel_length = descriptor.segment_count();
+    let pixel_stride = pixel_length + (data[3] as usize & 3);
+    let row_pitch = columns * pixel_stride + (data[3] as usize >> 2);
+    let encoded = &data[4..];
+
+    let mut pitched = vec![0; pitched_length(&descriptor, row_pitch, Some(pixel_stride)).unwrap()];
+    let result = decode_pitched(
+        encoded,
+        &descriptor,
+        &mut pitched,
+        row_pitch,
+        Some(pixel_stride),
+    );
+    let frame = decode_frame(encoded, &descriptor);
+    match (result, frame) {
+        (Ok(_), Ok((frame, _))) => {
+            for (row, frame_row) in frame.chunks(columns * pixel_length).enumerate() {
+                for (column, pixel) in frame_row.chunks(pixel_length).enumerate() {
+                    let start = row * row_pitch + column * pixel_stride;
+                    assert!(pixel == &pitched[start..start + pixel_length]);
+                }
+            }
+        }
+        (Ok(_), Err(error)) => panic!("only the pitched decode succeeded: {}", error),
+        (Err(_), _) => {}
+    }
+});
@@ -0,0 +1,47 @@
+// Decodes a region of arbitrary input and checks it matches the same region of
+// the whole decoded image.  The first six bytes choose the image layout and
+// the region, the rest is the encoded image.
+#![no_main]
+use dicomrle::decode::decode_frame;
+use dicomrle::descriptor::ImageDescriptor;
+use dicomrle::region::decode_region_frame;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 6 {
+        return;
+    }
+    let rows = data[0] as usize % 32 + 1;
+    let columns = data[1] as usize % 32 + 1;
+    let (samples_per_pixel, bits_allocated) = match data[2] % 3 {
+        0 => (1, 8),
+        1 => (1, 16),
+        _ => (3, 8),
+    };
+    let descriptor = ImageDescriptor::new(rows, columns, samples_per_pixel, bits_allocated);
+    let region_rows = data[3] as usize % rows..rows;
+    let region_columns = data[4] as usize % columns..(data[5] as usize % columns) + 1;
+    let encoded = &data[6..];
+
+    let region = decode_region_frame(
+        encoded,
+        &descriptor,
+        region_rows.clone(),
+        Some(region_columns.clone()),
+    );
+    let frame = decode_frame(encoded, &descriptor);
+    match (region, frame) {
+        (Ok((region, _)), Ok((frame, _))) => {
+            let pixel_length = descriptor.segment_count();
+            let mut expected = Vec::new();
+            for row in region_rows {
+                let start = (row * columns + region_columns.start) * pixel_length;
+                let end = (row * columns + region_columns.end) * pixel_length;
+                expected.extend_from_slice(&frame[start..end]);
+            }
+            assert!(region == expected);
+        }
+        (Ok(_), Err(error)) => panic!("only the region decoded: {}", error),
+        (Err(_), _) => {}
+    }
+});
@@ -0,0 +1,28 @@
+// Decodes an arbitrary segment with an arbitrary increment and checks that the
+// fast paths agree with the generic loop.  The first three bytes choose the
+// decoded length and the increment, the rest is the encoded segment.
+#![no_main]
+use dicomrle::decode_segment::{decode_segment, decode_segment_generic};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 3 {
+        return;
+    }
+    let decoded_length = u16::from_le_bytes([data[0], data[1]]) as usize;
+    // increments above 16 are mapped to huge values to exercise overflow
+    let increment = match data[2] {
+        increment @ 0..=16 => increment as usize,
+        shift => usize::MAX >> (shift % 64),
+    };
+    let segment = &data[3..];
+
+    let mut decoded = vec![0; decoded_length];
+    let result = decode_segment(segment, &mut decoded, increment);
+
+    let mut generic_decoded = vec![0; decoded_length];
+    let generic_result = decode_segment_generic(segment, &mut generic_decoded, increment);
+
+    assert_eq!(result, generic_result);
+    assert!(decoded == generic_decoded);
+});
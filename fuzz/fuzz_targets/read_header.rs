@@ -0,0 +1,16 @@
+// Parses an arbitrary header and checks that the offsets it returns are valid
+#![no_main]
+use dicomrle::get_segments::get_segments;
+use dicomrle::header::read_header;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(segment_offsets) = read_header(data) {
+        assert!((1..=15).contains(&segment_offsets.len()));
+        assert_eq!(64, segment_offsets[0]);
+        assert!(segment_offsets.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let segments = get_segments(data).unwrap();
+        assert_eq!(segment_offsets.len(), segments.len());
+    }
+});
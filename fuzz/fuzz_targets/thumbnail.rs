@@ -0,0 +1,45 @@
+// Decodes thumbnails of arbitrary input and checks the nearest thumbnail
+// matches every factor-th pixel of the whole decoded image.  The first four
+// bytes choose the image layout and the factor, the rest is the encoded image.
+#![no_main]
+use dicomrle::decode::decode_frame;
+use dicomrle::descriptor::ImageDescriptor;
+use dicomrle::thumbnail::{decode_thumbnail, thumbnail_descriptor, Downsample};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+    let rows = data[0] as usize % 32 + 1;
+    let columns = data[1] as usize % 32 + 1;
+    let (samples_per_pixel, bits_allocated) = match data[2] % 4 {
+        0 => (1, 8),
+        1 => (1, 16),
+        2 => (3, 8),
+        _ => (1, 32),
+    };
+    let descriptor = ImageDescriptor::new(rows, columns, samples_per_pixel, bits_allocated);
+    let factor = data[3] as usize % 8 + 1;
+    let encoded = &data[4..];
+
+    let _ = decode_thumbnail(encoded, &descriptor, factor, Downsample::Average);
+    let _ = decode_thumbnail(encoded, &descriptor, factor, Downsample::AverageSigned);
+
+    let thumbnail = decode_thumbnail(encoded, &descriptor, factor, Downsample::Nearest);
+    let frame = decode_frame(encoded, &descriptor);
+    match (thumbnail, frame) {
+        (Ok((thumbnail, _)), Ok((frame, _))) => {
+            let pixel_length = descriptor.segment_count();
+            let thumbnail_columns = thumbnail_descriptor(&descriptor, factor).columns;
+            for (index, pixel) in thumbnail.chunks(pixel_length).enumerate() {
+                let row = index / thumbnail_columns * factor;
+                let column = index % thumbnail_columns * factor;
+                let start = (row * columns + column) * pixel_length;
+                assert!(pixel == &frame[start..start + pixel_length]);
+            }
+        }
+        (Ok(_), Err(error)) => panic!("only the thumbnail decoded: {}", error),
+        (Err(_), _) => {}
+    }
+});
@@ -0,0 +1,199 @@
+/* C ABI for dicomrle-rs, see src/ffi.rs */
+
+#ifndef DICOMRLE_H
+#define DICOMRLE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The maximum number of segments in a DICOM RLE Image
+ */
+#define DICOMRLE_MAX_SEGMENTS 15
+
+/**
+ * Status codes returned by the C API.  Errors are returned as status codes
+ * rather than allowing panics to cross the FFI boundary.
+ */
+typedef enum DicomRleStatus {
+  /**
+   * the call succeeded
+   */
+  DICOM_RLE_STATUS_OK = 0,
+  /**
+   * a required pointer argument was null
+   */
+  DICOM_RLE_STATUS_NULL_POINTER = 1,
+  /**
+   * the image is not formatted properly
+   */
+  DICOM_RLE_STATUS_FORMAT_ERROR = 2,
+  /**
+   * the DICOM Part 10 file could not be read
+   */
+  DICOM_RLE_STATUS_PART10_ERROR = 3,
+  /**
+   * the image could not be exported
+   */
+  DICOM_RLE_STATUS_EXPORT_ERROR = 4,
+  /**
+   * an unexpected panic was caught, this indicates a bug in the library
+   */
+  DICOM_RLE_STATUS_PANIC = 5,
+  /**
+   * a value read from the image exceeded a decode limit
+   */
+  DICOM_RLE_STATUS_LIMIT_EXCEEDED = 6,
+} DicomRleStatus;
+
+/**
+ * Diagnostic information about a single decoded segment.  See
+ * `DecodeSegmentResult` for a description of each field.
+ */
+typedef struct DicomRleSegmentDiagnostics {
+  size_t bytes_decoded;
+  size_t bytes_consumed;
+  bool literal_run_underflow;
+  bool replicated_run_underflow;
+  bool decoded_overflow;
+  bool invalid_prefix;
+} DicomRleSegmentDiagnostics;
+
+/**
+ * Diagnostic information related to decoding an RLE image.  Only the first
+ * `segment_count` entries of `segments` are valid.
+ */
+typedef struct DicomRleDiagnostics {
+  bool incomplete_decode;
+  uint32_t segment_count;
+  struct DicomRleSegmentDiagnostics segments[DICOMRLE_MAX_SEGMENTS];
+} DicomRleDiagnostics;
+
+/**
+ * Describes which bits of each 16 bit sample hold the stored value, see
+ * `StoredBits`.
+ */
+typedef struct DicomRleStoredBits {
+  /**
+   * Bits Stored (0028,0101)
+   */
+  size_t bits_stored;
+  /**
+   * High Bit (0028,0102)
+   */
+  size_t high_bit;
+  /**
+   * Pixel Representation (0028,0103), 0 for unsigned and 1 for signed
+   */
+  size_t pixel_representation;
+} DicomRleStoredBits;
+
+/**
+ * The contents of a DICOM RLE header.  Only the first `segment_count`
+ * entries of `segment_offsets` are valid.
+ */
+typedef struct DicomRleHeader {
+  uint32_t segment_count;
+  uint32_t segment_offsets[DICOMRLE_MAX_SEGMENTS];
+} DicomRleHeader;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Decodes a DICOM RLE Image.  `diagnostics` may be null if not needed.
+ *
+ * # Safety
+ *
+ * `encoded` must point to `encoded_length` readable bytes and `decoded` must
+ * point to `decoded_length` writable bytes.  `diagnostics` must be null or
+ * point to a writable `DicomRleDiagnostics`.
+ */
+enum DicomRleStatus dicomrle_decode(const uint8_t *encoded,
+                                    size_t encoded_length,
+                                    uint8_t *decoded,
+                                    size_t decoded_length,
+                                    struct DicomRleDiagnostics *diagnostics);
+
+/**
+ * Decodes a 16 bit unsigned grayscale DICOM RLE Image.  `decoded_length` is
+ * the number of samples, not bytes, and must equal `rows * columns`.  Images
+ * with more than one sample per pixel are rejected with
+ * `DicomRleStatus::FormatError`.  If `stored_bits` is not null the samples
+ * are normalized like `normalize_u16()`, masking off the bits above High
+ * Bit and sign extending signed values.  `diagnostics` may be null if not
+ * needed.
+ *
+ * # Safety
+ *
+ * `encoded` must point to `encoded_length` readable bytes and `decoded` must
+ * point to `decoded_length` writable and aligned samples.  `stored_bits` must
+ * be null or point to a readable `DicomRleStoredBits` and `diagnostics` must
+ * be null or point to a writable `DicomRleDiagnostics`.
+ */
+enum DicomRleStatus dicomrle_decode_u16(const uint8_t *encoded,
+                                        size_t encoded_length,
+                                        size_t rows,
+                                        size_t columns,
+                                        size_t samples_per_pixel,
+                                        const struct DicomRleStoredBits *stored_bits,
+                                        uint16_t *decoded,
+                                        size_t decoded_length,
+                                        struct DicomRleDiagnostics *diagnostics);
+
+/**
+ * Decodes a 16 bit signed grayscale DICOM RLE Image.  `decoded_length` is
+ * the number of samples, not bytes, and must equal `rows * columns`.  Images
+ * with more than one sample per pixel are rejected with
+ * `DicomRleStatus::FormatError`.  If `stored_bits` is not null the samples
+ * are normalized like `normalize_i16()`, masking off the bits above High
+ * Bit and sign extending signed values.  `diagnostics` may be null if not
+ * needed.
+ *
+ * # Safety
+ *
+ * `encoded` must point to `encoded_length` readable bytes and `decoded` must
+ * point to `decoded_length` writable and aligned samples.  `stored_bits` must
+ * be null or point to a readable `DicomRleStoredBits` and `diagnostics` must
+ * be null or point to a writable `DicomRleDiagnostics`.
+ */
+enum DicomRleStatus dicomrle_decode_i16(const uint8_t *encoded,
+                                        size_t encoded_length,
+                                        size_t rows,
+                                        size_t columns,
+                                        size_t samples_per_pixel,
+                                        const struct DicomRleStoredBits *stored_bits,
+                                        int16_t *decoded,
+                                        size_t decoded_length,
+                                        struct DicomRleDiagnostics *diagnostics);
+
+/**
+ * Reads the header of a DICOM RLE Image
+ *
+ * # Safety
+ *
+ * `encoded` must point to `encoded_length` readable bytes and `header` must
+ * point to a writable `DicomRleHeader`.
+ */
+enum DicomRleStatus dicomrle_read_header(const uint8_t *encoded,
+                                         size_t encoded_length,
+                                         struct DicomRleHeader *header);
+
+/**
+ * Returns a static, NUL terminated description of a status code.  The status
+ * is taken as an integer since C callers can pass any value, values that are
+ * not a `DicomRleStatus` are described as an unknown status.
+ */
+const char *dicomrle_status_message(uint32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DICOMRLE_H */
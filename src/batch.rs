@@ -0,0 +1,353 @@
+use crate::decode::{check_segment_count, checked_decoded_length, decode_segments};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::limits::DecodeLimits;
+use rayon::prelude::*;
+use std::mem;
+
+/// The diagnostics or error for one frame decoded by a `BatchDecoder`
+pub type FrameResult = Result<DecodeDiagnostics, Error>;
+
+/// Decodes the frames of a multi-frame image in parallel.  The frames are
+/// decoded on the rayon thread pool, one frame per task, with the segments of
+/// each frame decoded serially directly into the output so no scratch buffers
+/// are allocated per frame.
+pub struct BatchDecoder {
+    descriptor: ImageDescriptor,
+
+    /// the output volume, reused across calls to decode_volume()
+    volume: Vec<u8>,
+
+    limits: DecodeLimits,
+}
+
+// decodes one frame, checking its size against the limits and the number of
+// segments against the descriptor
+fn decode_one(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+    decoded: &mut [u8],
+) -> Result<DecodeDiagnostics, Error> {
+    limits.check_encoded(encoded)?;
+    check_segment_count(encoded, descriptor)?;
+    let segments = get_segments(encoded)?;
+    Ok(decode_segments(&segments, decoded))
+}
+
+// splits the volume into one buffer per frame
+fn split_frames(volume: &mut [u8], frame_length: usize, frame_count: usize) -> Vec<&mut [u8]> {
+    let mut rest = volume;
+    (0..frame_count)
+        .map(|_| {
+            let (frame, tail) = mem::take(&mut rest).split_at_mut(frame_length);
+            rest = tail;
+            frame
+        })
+        .collect()
+}
+
+impl BatchDecoder {
+    /// Creates a decoder for frames with the given layout
+    pub fn new(descriptor: ImageDescriptor) -> BatchDecoder {
+        BatchDecoder {
+            descriptor,
+            volume: Vec::new(),
+            limits: DecodeLimits::unlimited(),
+        }
+    }
+
+    /// Creates a decoder for frames with the given layout that enforces the
+    /// limits.  Returns `Error::LimitExceeded` if the descriptor exceeds them.
+    /// The number of frames and the size of the volume are checked by
+    /// decode_volume() and the size of each encoded frame is checked before it
+    /// is decoded.
+    pub fn with_limits(
+        descriptor: ImageDescriptor,
+        limits: DecodeLimits,
+    ) -> Result<BatchDecoder, Error> {
+        limits.check_descriptor(&descriptor)?;
+        Ok(BatchDecoder {
+            descriptor,
+            volume: Vec::new(),
+            limits,
+        })
+    }
+
+    /// Returns the layout of each decoded frame
+    pub fn descriptor(&self) -> &ImageDescriptor {
+        &self.descriptor
+    }
+
+    /// Decodes the frames in parallel into one contiguous volume with frame
+    /// `i` starting at byte `i * descriptor.decoded_length()`.  The volume is
+    /// owned by the decoder and reused across calls so decoding a series of
+    /// clips only allocates when a clip has more frames than any before it.
+    /// Bytes that could not be decoded are zero.
+    ///
+    /// Returns the volume and the diagnostics or error for each frame.  An
+    /// error in one frame does not stop the other frames from being decoded.
+    /// Returns `Error::LimitExceeded` without decoding anything if there are
+    /// more frames or the volume is larger than the limits allow, or
+    /// `Error::Format` if the volume is too large to allocate.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The encoded RLE frames
+    ///
+    pub fn decode_volume(&mut self, frames: &[&[u8]]) -> Result<(&[u8], Vec<FrameResult>), Error> {
+        self.limits.check_frames(frames.len())?;
+
+        let frame_length = checked_decoded_length(&self.descriptor)?;
+        self.limits.check_volume(frames.len(), frame_length)?;
+        let volume_length = frames
+            .len()
+            .checked_mul(frame_length)
+            .filter(|length| *length <= isize::MAX as usize)
+            .ok_or_else(|| {
+                Error::Format(format!(
+                    "{} frames of {} bytes are too large to decode",
+                    frames.len(),
+                    frame_length
+                ))
+            })?;
+        self.volume.resize(volume_length, 0);
+
+        let descriptor = &self.descriptor;
+        let limits = &self.limits;
+        let mut buffers = split_frames(&mut self.volume, frame_length, frames.len());
+        let results = frames
+            .par_iter()
+            .zip(buffers.par_iter_mut())
+            .map(|(encoded, decoded)| {
+                // clear data left over from the previous call
+                decoded.fill(0);
+                decode_one(encoded, descriptor, limits, decoded)
+            })
+            .collect();
+
+        Ok((&self.volume, results))
+    }
+
+    /// Decodes the frames in parallel into caller provided buffers, one per
+    /// frame.  As with decode(), bytes that could not be decoded are left as
+    /// is.
+    ///
+    /// Returns the diagnostics or error for each frame.  An error in one frame
+    /// does not stop the other frames from being decoded.  Returns
+    /// `Error::Format` without decoding anything if the number of buffers does
+    /// not match the number of frames or a buffer is not the decoded frame
+    /// size.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames`  - The encoded RLE frames
+    ///
+    /// * `buffers` - The decoded buffer for each frame, each sized to the
+    ///   decoded frame size
+    ///
+    pub fn decode_into(
+        &self,
+        frames: &[&[u8]],
+        buffers: &mut [&mut [u8]],
+    ) -> Result<Vec<FrameResult>, Error> {
+        if frames.len() != buffers.len() {
+            return Err(Error::Format(format!(
+                "expected one buffer per frame but found {} buffers for {} frames",
+                buffers.len(),
+                frames.len()
+            )));
+        }
+        let frame_length = checked_decoded_length(&self.descriptor)?;
+        if let Some((index, buffer)) = buffers
+            .iter()
+            .enumerate()
+            .find(|(_, buffer)| buffer.len() != frame_length)
+        {
+            return Err(Error::Format(format!(
+                "expected buffer {} to be {} bytes but found {}",
+                index,
+                frame_length,
+                buffer.len()
+            )));
+        }
+
+        Ok(frames
+            .par_iter()
+            .zip(buffers.par_iter_mut())
+            .map(|(encoded, decoded)| decode_one(encoded, &self.descriptor, &self.limits, decoded))
+            .collect())
+    }
+
+    /// Consumes the decoder and returns the volume from the last call to
+    /// decode_volume()
+    pub fn into_volume(self) -> Vec<u8> {
+        self.volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchDecoder;
+    use crate::decode::decode_frame;
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::limits::{DecodeLimits, Limit};
+    use std::fs;
+
+    fn read_us1() -> Vec<u8> {
+        fs::read("tests/rleimage/us1.rle").unwrap()
+    }
+
+    #[test]
+    fn decode_volume_matches_decode_frame() {
+        let encoded = read_us1();
+        let truncated = &encoded[..encoded.len() / 2];
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let frames = vec![&encoded[..], truncated, &encoded[..]];
+
+        let mut decoder = BatchDecoder::new(descriptor);
+        let (volume, results) = decoder.decode_volume(&frames).unwrap();
+
+        assert_eq!(3, results.len());
+        let frame_length = descriptor.decoded_length();
+        for (index, frame) in frames.iter().enumerate() {
+            let (expected, diagnostics) = decode_frame(frame, &descriptor).unwrap();
+            assert_eq!(&diagnostics, results[index].as_ref().unwrap());
+            assert!(expected[..] == volume[index * frame_length..(index + 1) * frame_length]);
+        }
+        assert!(results[1].as_ref().unwrap().incomplete_decode);
+    }
+
+    #[test]
+    fn decode_volume_reuses_and_clears_volume() {
+        let encoded = read_us1();
+        let truncated = &encoded[..encoded.len() / 2];
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let mut decoder = BatchDecoder::new(descriptor);
+
+        decoder
+            .decode_volume(&[&encoded[..], &encoded[..]])
+            .unwrap();
+        let (volume, _) = decoder.decode_volume(&[truncated]).unwrap();
+
+        let (expected, _) = decode_frame(truncated, &descriptor).unwrap();
+        assert!(expected[..] == volume[..]);
+    }
+
+    #[test]
+    fn decode_volume_reports_errors_per_frame() {
+        let encoded = read_us1();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let mut decoder = BatchDecoder::new(descriptor);
+
+        let (_, results) = decoder
+            .decode_volume(&[&encoded[..10], &encoded[..]])
+            .unwrap();
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+    }
+
+    #[test]
+    fn decode_into_caller_buffers() {
+        let encoded = read_us1();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let decoder = BatchDecoder::new(descriptor);
+        let mut first = vec![0; descriptor.decoded_length()];
+        let mut second = vec![0; descriptor.decoded_length()];
+
+        let results = decoder
+            .decode_into(
+                &[&encoded[..], &encoded[..]],
+                &mut [&mut first[..], &mut second[..]],
+            )
+            .unwrap();
+
+        let (expected, _) = decode_frame(&encoded, &descriptor).unwrap();
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert!(expected == first);
+        assert!(expected == second);
+    }
+
+    #[test]
+    fn limits() {
+        let encoded = read_us1();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let limits = DecodeLimits {
+            max_frames: 2,
+            max_encoded_bytes: encoded.len() - 1,
+            ..DecodeLimits::default()
+        };
+        let mut decoder = BatchDecoder::with_limits(descriptor, limits).unwrap();
+
+        let frames = [&encoded[..], &encoded[..100]];
+        let (_, results) = decoder.decode_volume(&frames).unwrap();
+        assert!(matches!(
+            results[0],
+            Err(Error::LimitExceeded {
+                limit: Limit::EncodedBytes,
+                ..
+            })
+        ));
+        assert!(results[1].is_ok());
+
+        assert!(decoder.decode_volume(&[&encoded[..]; 3]).is_err());
+
+        let limits = DecodeLimits {
+            max_volume_bytes: descriptor.decoded_length() * 2 - 1,
+            ..limits
+        };
+        let mut decoder = BatchDecoder::with_limits(descriptor, limits).unwrap();
+        assert!(decoder.decode_volume(&[&encoded[..]]).is_ok());
+        assert!(matches!(
+            decoder.decode_volume(&[&encoded[..]; 2]),
+            Err(Error::LimitExceeded {
+                limit: Limit::VolumeBytes,
+                ..
+            })
+        ));
+
+        let limits = DecodeLimits {
+            max_rows: 479,
+            ..limits
+        };
+        assert!(BatchDecoder::with_limits(descriptor, limits).is_err());
+    }
+
+    #[test]
+    fn decode_volume_too_large_to_allocate() {
+        let encoded = read_us1();
+        let descriptor = ImageDescriptor::new(usize::MAX / 8, 1, 3, 8);
+        let mut decoder = BatchDecoder::new(descriptor);
+        assert!(matches!(
+            decoder.decode_volume(&[&encoded[..]; 3]),
+            Err(Error::Format(_))
+        ));
+    }
+
+    #[test]
+    fn decode_into_with_missing_buffer_is_an_error() {
+        let encoded = read_us1();
+        let decoder = BatchDecoder::new(ImageDescriptor::new(480, 640, 3, 8));
+        let result = decoder.decode_into(&[&encoded[..]], &mut []);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn decode_into_with_wrong_buffer_size_is_an_error() {
+        let encoded = read_us1();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let decoder = BatchDecoder::new(descriptor);
+        let mut first = vec![0; descriptor.decoded_length()];
+        let mut second = vec![0; descriptor.decoded_length() - 1];
+
+        let result = decoder.decode_into(
+            &[&encoded[..], &encoded[..]],
+            &mut [&mut first[..], &mut second[..]],
+        );
+        assert!(matches!(result, Err(Error::Format(_))));
+        assert!(first.iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,163 @@
+//! Extracts a range of rows from an RLE image without decoding it.  Encoders
+//! are expected to encode each row separately, so the runs of the rows to
+//! keep can be copied to the new image as they are.
+
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::header::write_header;
+use crate::tokens::tokens;
+use std::ops::Range;
+
+/// Returns the part of an RLE segment that decodes to the given range of
+/// rows.  Runs crossing rows inside the range are copied as they are, but an
+/// error is returned if a run crosses the first or last row boundary of the
+/// range, or the segment ends before the last row.
+///
+/// # Arguments
+///
+/// * `segment` - The encoded RLE segment
+///
+/// * `columns` - The number of columns in the image
+///
+/// * `rows`    - The range of rows to extract
+///
+pub fn crop_segment(segment: &[u8], columns: usize, rows: Range<usize>) -> Result<&[u8], Error> {
+    if columns == 0 || rows.start >= rows.end {
+        return Err(Error::Format(format!(
+            "cannot crop rows {}..{} with {} columns",
+            rows.start, rows.end, columns
+        )));
+    }
+    let too_large = || Error::Format(format!("rows {}..{} are too large", rows.start, rows.end));
+    let start = rows.start.checked_mul(columns).ok_or_else(too_large)?;
+    let end = rows.end.checked_mul(columns).ok_or_else(too_large)?;
+
+    let mut decoded_index = 0;
+    let mut first = None;
+    let mut runs = tokens(segment);
+    loop {
+        if decoded_index == start {
+            first = Some(runs.position());
+        }
+        if decoded_index == end {
+            break;
+        }
+
+        let position = runs.position();
+        let run_length = runs.next().map_or(0, |token| token.decoded_len());
+        if run_length == 0 {
+            return Err(Error::Format(format!(
+                "segment ends at offset {} before the end of row {}",
+                position,
+                rows.end - 1
+            )));
+        }
+
+        let run_end = decoded_index + run_length;
+        for boundary in [start, end] {
+            if decoded_index < boundary && run_end > boundary {
+                return Err(Error::Format(format!(
+                    "run at offset {} crosses the start of row {}",
+                    position,
+                    boundary / columns
+                )));
+            }
+        }
+        decoded_index = run_end;
+    }
+
+    // start < end so first is always set before the end is reached
+    let first = first.unwrap_or(0);
+    Ok(&segment[first..runs.position()])
+}
+
+/// Extracts a range of rows from a DICOM RLE Image and returns them as a new
+/// RLE image with its own header.  Works with any number of segments.
+/// Returns an error if the header is invalid or any segment cannot be cropped,
+/// see `crop_segment()`.
+///
+/// # Arguments
+///
+/// * `encoded` - The encoded RLE image
+///
+/// * `columns` - The number of columns in the image
+///
+/// * `rows`    - The range of rows to extract
+///
+pub fn crop_rows(encoded: &[u8], columns: usize, rows: Range<usize>) -> Result<Vec<u8>, Error> {
+    let mut segments = Vec::new();
+    for (segment_index, segment) in get_segments(encoded)?.iter().enumerate() {
+        let cropped = crop_segment(segment, columns, rows.clone())
+            .map_err(|error| Error::Format(format!("segment {}: {}", segment_index, error)))?;
+        segments.push(cropped);
+    }
+
+    // segments must have an even length as per the DICOM standard
+    let padded_length = |segment: &&[u8]| segment.len() + segment.len() % 2;
+    let segment_lengths: Vec<usize> = segments.iter().map(padded_length).collect();
+    let mut cropped = write_header(&segment_lengths)?;
+    for segment in segments {
+        cropped.extend_from_slice(segment);
+        if segment.len() % 2 == 1 {
+            cropped.push(0);
+        }
+    }
+
+    Ok(cropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crop_rows, crop_segment};
+    use crate::decode::decode;
+    use crate::error::Error;
+    use crate::test::tests::make_header;
+
+    // 3 rows of 4 columns, each row encoded separately
+    const SEGMENT: [u8; 10] = [253, 1, 1, 2, 3, 255, 4, 253, 5, 0];
+
+    #[test]
+    fn crop_segment_rows() {
+        assert_eq!(&[253, 1], crop_segment(&SEGMENT, 4, 0..1).unwrap());
+        assert_eq!(&[1, 2, 3, 255, 4], crop_segment(&SEGMENT, 4, 1..2).unwrap());
+        assert_eq!(&SEGMENT[2..9], crop_segment(&SEGMENT, 4, 1..3).unwrap());
+    }
+
+    #[test]
+    fn run_crossing_rows_is_an_error() {
+        // the replicated run covers the end of row 0 and the start of row 1
+        let result = crop_segment(&[1, 1, 2, 253, 3, 1, 4, 5], 4, 1..2);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn run_crossing_rows_inside_the_range() {
+        let segment = [1, 1, 2, 253, 3, 1, 4, 5];
+        assert_eq!(&segment[..], crop_segment(&segment, 4, 0..2).unwrap());
+    }
+
+    #[test]
+    fn rows_past_the_end_is_an_error() {
+        let result = crop_segment(&SEGMENT, 4, 2..4);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn empty_range_is_an_error() {
+        let result = crop_segment(&SEGMENT, 4, 1..1);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn crop_two_segments() {
+        let mut encoded = make_header(&mut vec![2, 64, 74]);
+        encoded.extend_from_slice(&SEGMENT);
+        encoded.extend_from_slice(&[253, 9, 253, 8, 253, 7]);
+
+        let cropped = crop_rows(&encoded, 4, 1..2).unwrap();
+        let mut decoded = vec![0; 8];
+        let diagnostics = decode(&cropped, &mut decoded).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![8, 2, 8, 3, 8, 4, 8, 4], decoded);
+    }
+}
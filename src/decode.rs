@@ -1,13 +1,19 @@
 use crate::decode_segment::*;
+use crate::descriptor::ImageDescriptor;
 use crate::diagnostics::DecodeDiagnostics;
 use crate::error::Error;
 use crate::get_segments::get_segments;
+use crate::header::read_header;
+use crate::interleave::{interleave2, interleave3, interleave_portable};
+use crate::limits::DecodeLimits;
+use crate::tokens::{tokens, Token, Tokens};
+use std::mem::MaybeUninit;
 use std::slice;
 
 // If two segments, we assume we have 16 bit grayscale data which requires us to
 // read MSB first followed by LSB.  If not two segments, we just do normal byte
 // ordering for 8 bit grayscale and 8 bit color images
-fn calculate_start_index(segment_count: usize, segment_index: usize) -> usize {
+pub(crate) fn calculate_start_index(segment_count: usize, segment_index: usize) -> usize {
     if segment_count == 2 {
         segment_count - 1 - segment_index
     } else {
@@ -15,18 +21,21 @@ fn calculate_start_index(segment_count: usize, segment_index: usize) -> usize {
     }
 }
 
-/// Decodes a DICOM RLE Image
-///
-/// # Arguments
-///
-/// * `encoded`   - The encoded RLE image
-///
-/// * `decoded`   - The decoded buffer, presized to the expected image size
-///
-#[allow(dead_code)]
-pub fn decode(encoded: &[u8], decoded: &mut [u8]) -> Result<DecodeDiagnostics, Error> {
-    let segments = get_segments(encoded)?;
+/// Images with fewer decoded bytes than this are decoded serially even when
+/// the rayon feature is enabled since the cost of dispatching the segments to
+/// the thread pool outweighs the benefit
+#[cfg(feature = "rayon")]
+pub const PARALLEL_DECODE_THRESHOLD: usize = 256 * 1024;
+
+// the number of pixels decoded by each task in the parallel decode
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK_PIXELS: usize = 64 * 1024;
 
+// decodes each segment in turn directly into the decoded buffer
+pub(crate) fn decode_segments<T: DecodedByte>(
+    segments: &[&[u8]],
+    decoded: &mut [T],
+) -> DecodeDiagnostics {
     let mut decode_result = DecodeDiagnostics::new();
 
     let segment_count = segments.len();
@@ -34,7 +43,10 @@ pub fn decode(encoded: &[u8], decoded: &mut [u8]) -> Result<DecodeDiagnostics, E
     for (segment_index, segment) in segments.iter().enumerate() {
         let start_index = calculate_start_index(segment_count, segment_index);
 
-        let result = decode_segment(segment, &mut decoded[start_index..], segment_count);
+        // a decoded buffer shorter than the number of segments has no room for
+        // some segments, which then report an overflow instead of panicking
+        let segment_decoded = decoded.get_mut(start_index..).unwrap_or(&mut []);
+        let result = decode_segment_into(segment, segment_decoded, segment_count);
 
         if result.bytes_decoded != decoded.len() / segment_count {
             decode_result.incomplete_decode = true;
@@ -43,21 +55,1133 @@ pub fn decode(encoded: &[u8], decoded: &mut [u8]) -> Result<DecodeDiagnostics, E
         decode_result.decode_segment_results[segment_index] = Some(result);
     }
 
-    Ok(decode_result)
+    decode_result
+}
+
+/// How the segments of an image are decoded into the decoded buffer
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DecodeStrategy {
+    /// Each segment is decoded directly into the decoded buffer, writing every
+    /// n-th byte where n is the number of segments
+    Strided,
+
+    /// Each segment is decoded contiguously into a scratch plane and the planes
+    /// are then interleaved, using SIMD for 16 bit grayscale and 8 bit color
+    /// images when the CPU supports it
+    Planar,
+
+    /// All segments are decoded together a block of rows at a time so each
+    /// part of the decoded buffer is written by every segment while it is in
+    /// the cache.  This relies on each row being encoded separately as
+    /// required by the standard, images with runs that cross rows are decoded
+    /// again with the strided strategy.
+    RowBlocked {
+        /// the number of pixels in each row
+        columns: usize,
+    },
+}
+
+// a segment decoded contiguously along with the result of decoding it
+type Plane = (Vec<u8>, DecodeSegmentResult);
+
+// decodes a segment into a plane sized to the number of samples the segment
+// can write in the decoded buffer so the diagnostics are identical to
+// decoding it directly into the decoded buffer
+fn decode_plane(
+    segment: &[u8],
+    segment_index: usize,
+    segment_count: usize,
+    decoded_length: usize,
+) -> Plane {
+    let start_index = calculate_start_index(segment_count, segment_index);
+    let plane_length = decoded_length
+        .saturating_sub(start_index)
+        .div_ceil(segment_count);
+    let mut plane = vec![0; plane_length];
+    let result = decode_segment(segment, &mut plane, 1);
+    (plane, result)
+}
+
+fn diagnostics_from_planes(planes: &[Plane], decoded_length: usize) -> DecodeDiagnostics {
+    let mut decode_result = DecodeDiagnostics::new();
+    for (segment_index, (_, result)) in planes.iter().enumerate() {
+        if result.bytes_decoded != decoded_length / planes.len() {
+            decode_result.incomplete_decode = true;
+        }
+        decode_result.decode_segment_results[segment_index] = Some(*result);
+    }
+    decode_result
+}
+
+// interleaves the planes into the decoded buffer.  Pixels decoded by every
+// segment are interleaved with SIMD where possible and the remaining samples
+// are copied one at a time.  Only the decoded samples are copied so bytes past
+// the end of an incomplete segment are left untouched, as they are when
+// decoding directly into the decoded buffer.
+fn interleave_planes(planes: &[Plane], decoded: &mut [u8]) {
+    let segment_count = planes.len();
+    let complete = planes
+        .iter()
+        .map(|(_, result)| result.bytes_decoded)
+        .min()
+        .unwrap_or(0)
+        .min(decoded.len() / segment_count);
+    let complete_length = complete * segment_count;
+
+    match planes {
+        // the first segment is the most significant byte
+        [high, low] => interleave2(
+            &low.0[..complete],
+            &high.0[..complete],
+            &mut decoded[..complete_length],
+        ),
+        [first, second, third] => interleave3(
+            &first.0[..complete],
+            &second.0[..complete],
+            &third.0[..complete],
+            &mut decoded[..complete_length],
+        ),
+        _ => {
+            let complete_planes: Vec<&[u8]> =
+                planes.iter().map(|(plane, _)| &plane[..complete]).collect();
+            interleave_portable(&complete_planes, &mut decoded[..complete_length]);
+        }
+    }
+
+    for (segment_index, (plane, result)) in planes.iter().enumerate() {
+        let start_index = calculate_start_index(segment_count, segment_index);
+        for (byte, value) in decoded
+            .iter_mut()
+            .skip(complete_length + start_index)
+            .step_by(segment_count)
+            .zip(&plane[complete..result.bytes_decoded])
+        {
+            *byte = *value;
+        }
+    }
+}
+
+// decodes each segment into a separate plane and then interleaves the planes
+// into the decoded buffer
+fn decode_segments_planar(segments: &[&[u8]], decoded: &mut [u8]) -> DecodeDiagnostics {
+    let planes: Vec<Plane> = segments
+        .iter()
+        .enumerate()
+        .map(|(segment_index, segment)| {
+            decode_plane(segment, segment_index, segments.len(), decoded.len())
+        })
+        .collect();
+
+    interleave_planes(&planes, decoded);
+
+    diagnostics_from_planes(&planes, decoded.len())
+}
+
+// the number of decoded bytes in each block of rows decoded together by the
+// row blocked strategy
+const ROW_BLOCK_BYTES: usize = 64 * 1024;
+
+// the progress of a segment decoded a block of rows at a time
+struct SegmentCursor<'a> {
+    runs: Tokens<'a>,
+    segment: &'a [u8],
+    start_index: usize,
+    pixel_index: usize,
+    result: DecodeSegmentResult,
+    overflowed: bool,
+}
+
+// returned when a run crosses the end of a block of rows
+struct RowCrossed;
+
+impl<'a> SegmentCursor<'a> {
+    fn new(segment: &'a [u8], start_index: usize) -> SegmentCursor<'a> {
+        SegmentCursor {
+            runs: tokens(segment),
+            segment,
+            start_index,
+            pixel_index: 0,
+            result: DecodeSegmentResult::default(),
+            overflowed: false,
+        }
+    }
+
+    // decodes runs up to the end of the block with the same checks as
+    // decode_segment().  In the last block decoding continues to the end of
+    // the segment and runs past the end of the block are overflows, in other
+    // blocks a run past the end of the block crosses a row.  INCREMENT is the
+    // number of segments if it is known at compile time or zero otherwise.
+    fn decode_block<const INCREMENT: usize>(
+        &mut self,
+        decoded: &mut [u8],
+        segment_count: usize,
+        block_end: usize,
+        last_block: bool,
+    ) -> Result<(), RowCrossed> {
+        let increment = if INCREMENT == 0 {
+            segment_count
+        } else {
+            INCREMENT
+        };
+        while !self.overflowed && (last_block || self.pixel_index < block_end) {
+            let token = match self.runs.next() {
+                Some(token) => token,
+                None => break,
+            };
+            let run_length = token.decoded_len();
+            if run_length == 0 {
+                self.result.record_stop(token);
+                break;
+            }
+            if self.pixel_index + run_length > block_end {
+                if last_block {
+                    self.result.decoded_overflow = true;
+                    self.overflowed = true;
+                    break;
+                }
+                return Err(RowCrossed);
+            }
+
+            let first_index = self.start_index + self.pixel_index * increment;
+            let last_index = first_index + (run_length - 1) * increment;
+            let destination = &mut decoded[first_index..=last_index];
+            match token {
+                Token::Literal { offset, len } => {
+                    let source = &self.segment[offset..offset + len];
+                    if increment == 1 {
+                        destination.copy_from_slice(source);
+                    } else {
+                        for (byte, value) in destination.iter_mut().step_by(increment).zip(source) {
+                            *byte = *value;
+                        }
+                    }
+                }
+                Token::Replicate { value, .. } => {
+                    if increment == 1 {
+                        destination.fill(value);
+                    } else {
+                        for byte in destination.iter_mut().step_by(increment) {
+                            *byte = value;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            self.pixel_index += run_length;
+            self.result.bytes_consumed = self.runs.position();
+        }
+        Ok(())
+    }
+}
+
+// decodes all segments together a block of rows at a time.  Falls back to
+// decode_segments() if the decoded buffer is not a whole number of rows or a
+// run crosses the end of a block.  The bytes written before falling back are a
+// prefix of what decode_segments() writes so the result is identical.
+fn decode_segments_row_blocked(
+    segments: &[&[u8]],
+    decoded: &mut [u8],
+    columns: usize,
+) -> DecodeDiagnostics {
+    let segment_count = segments.len();
+    let row_length = match columns.checked_mul(segment_count) {
+        Some(row_length) if row_length > 0 => row_length,
+        _ => return decode_segments(segments, decoded),
+    };
+    if decoded.is_empty() || !decoded.len().is_multiple_of(row_length) {
+        return decode_segments(segments, decoded);
+    }
+    let rows = decoded.len() / row_length;
+    let block_rows = (ROW_BLOCK_BYTES / row_length).max(1);
+
+    let mut cursors: Vec<SegmentCursor> = segments
+        .iter()
+        .enumerate()
+        .map(|(segment_index, segment)| {
+            SegmentCursor::new(segment, calculate_start_index(segment_count, segment_index))
+        })
+        .collect();
+
+    let mut block_end_row = 0;
+    loop {
+        block_end_row = (block_end_row + block_rows).min(rows);
+        let last_block = block_end_row == rows;
+        let block_end = block_end_row * columns;
+        for cursor in cursors.iter_mut() {
+            let crossed = match segment_count {
+                1 => cursor.decode_block::<1>(decoded, segment_count, block_end, last_block),
+                2 => cursor.decode_block::<2>(decoded, segment_count, block_end, last_block),
+                3 => cursor.decode_block::<3>(decoded, segment_count, block_end, last_block),
+                _ => cursor.decode_block::<0>(decoded, segment_count, block_end, last_block),
+            };
+            if crossed.is_err() {
+                return decode_segments(segments, decoded);
+            }
+        }
+        if last_block {
+            break;
+        }
+    }
+
+    let mut decode_result = DecodeDiagnostics::new();
+    for (segment_index, cursor) in cursors.iter().enumerate() {
+        let mut result = cursor.result;
+        result.bytes_decoded = cursor.pixel_index;
+        if result.bytes_decoded != decoded.len() / segment_count {
+            decode_result.incomplete_decode = true;
+        }
+        decode_result.decode_segment_results[segment_index] = Some(result);
+    }
+    decode_result
+}
+
+// the runs of a segment found without decoding them, so the parallel decode
+// can start decoding the segment part way through
+#[cfg(feature = "rayon")]
+struct SegmentScan {
+    // identical to the result of decode_segment()
+    result: DecodeSegmentResult,
+
+    // for each chunk of pixels with at least one decoded sample, the position
+    // of the control byte of the run holding the first pixel of the chunk and
+    // the number of samples of that run before the chunk
+    chunk_starts: Vec<(usize, usize)>,
+}
+
+// scans the runs of a segment with the same checks as decode_segment(), where
+// capacity is the number of samples the segment can write in the decoded
+// buffer
+#[cfg(feature = "rayon")]
+fn scan_segment(segment: &[u8], capacity: usize) -> SegmentScan {
+    let mut result = DecodeSegmentResult::default();
+    let mut chunk_starts = Vec::new();
+    let mut pixel_index = 0;
+
+    let mut runs = tokens(segment);
+    loop {
+        let position = runs.position();
+        let token = match runs.next() {
+            Some(token) => token,
+            None => break,
+        };
+        let run_length = token.decoded_len();
+        if run_length == 0 {
+            result.record_stop(token);
+            break;
+        }
+        if pixel_index + run_length > capacity {
+            result.decoded_overflow = true;
+            break;
+        }
+
+        let run_end = pixel_index + run_length;
+        while chunk_starts.len() * PARALLEL_CHUNK_PIXELS < run_end {
+            let chunk_start = chunk_starts.len() * PARALLEL_CHUNK_PIXELS;
+            chunk_starts.push((position, chunk_start - pixel_index));
+        }
+
+        pixel_index = run_end;
+        result.bytes_consumed = runs.position();
+    }
+
+    result.bytes_decoded = pixel_index;
+    SegmentScan {
+        result,
+        chunk_starts,
+    }
+}
+
+// writes `samples` decoded samples of a segment into every increment-th byte
+// of decoded, starting `skip` samples into the first run.  The segment has
+// been scanned so every run up to the last sample is valid and fits.
+// INCREMENT is the number of segments if it is known at compile time or zero
+// otherwise.
+#[cfg(feature = "rayon")]
+fn write_samples<T: DecodedByte, const INCREMENT: usize>(
+    segment: &[u8],
+    mut skip: usize,
+    samples: usize,
+    decoded: &mut [T],
+    segment_count: usize,
+) {
+    let increment = if INCREMENT == 0 {
+        segment_count
+    } else {
+        INCREMENT
+    };
+    let mut written = 0;
+    for token in tokens(segment) {
+        if written == samples {
+            break;
+        }
+        let len = (token.decoded_len() - skip).min(samples - written);
+        let first_index = written * increment;
+        let destination = &mut decoded[first_index..=first_index + (len - 1) * increment];
+        match token {
+            Token::Literal { offset, .. } => {
+                let source = &segment[offset + skip..offset + skip + len];
+                if increment == 1 {
+                    T::copy_values(destination, source);
+                } else {
+                    for (byte, value) in destination.iter_mut().step_by(increment).zip(source) {
+                        *byte = T::from_value(*value);
+                    }
+                }
+            }
+            Token::Replicate { value, .. } => {
+                if increment == 1 {
+                    destination.fill(T::from_value(value));
+                } else {
+                    for byte in destination.iter_mut().step_by(increment) {
+                        *byte = T::from_value(value);
+                    }
+                }
+            }
+            _ => break,
+        }
+        written += len;
+        skip = 0;
+    }
+}
+
+// scans the segments in parallel and then decodes the decoded buffer in
+// parallel chunks of pixels, each task writing every segment's samples for its
+// chunk straight into the decoded buffer.  Splitting the buffer rather than
+// the segments keeps threads from writing interleaved bytes of the same cache
+// lines and needs no scratch memory.  Only the decoded samples are written so
+// bytes past the end of an incomplete segment are left untouched, as they are
+// in the serial path.
+#[cfg(feature = "rayon")]
+fn decode_segments_parallel<T: DecodedByte>(
+    segments: &[&[u8]],
+    decoded: &mut [T],
+) -> DecodeDiagnostics {
+    use rayon::prelude::*;
+
+    let segment_count = segments.len();
+    let decoded_length = decoded.len();
+
+    let scans: Vec<SegmentScan> = segments
+        .par_iter()
+        .enumerate()
+        .map(|(segment_index, segment)| {
+            let start_index = calculate_start_index(segment_count, segment_index);
+            let capacity = decoded_length
+                .saturating_sub(start_index)
+                .div_ceil(segment_count);
+            scan_segment(segment, capacity)
+        })
+        .collect();
+
+    decoded
+        .par_chunks_mut(segment_count * PARALLEL_CHUNK_PIXELS)
+        .enumerate()
+        .for_each(|(chunk_index, chunk)| {
+            let first_pixel = chunk_index * PARALLEL_CHUNK_PIXELS;
+            for (segment_index, (segment, scan)) in segments.iter().zip(&scans).enumerate() {
+                let (position, skip) = match scan.chunk_starts.get(chunk_index) {
+                    Some(chunk_start) => *chunk_start,
+                    None => continue,
+                };
+                let last_pixel = scan
+                    .result
+                    .bytes_decoded
+                    .min(first_pixel + PARALLEL_CHUNK_PIXELS);
+                let samples = last_pixel - first_pixel;
+                let start_index = calculate_start_index(segment_count, segment_index);
+                let segment = &segment[position..];
+                let chunk = &mut chunk[start_index..];
+                match segment_count {
+                    2 => write_samples::<T, 2>(segment, skip, samples, chunk, segment_count),
+                    3 => write_samples::<T, 3>(segment, skip, samples, chunk, segment_count),
+                    _ => write_samples::<T, 0>(segment, skip, samples, chunk, segment_count),
+                }
+            }
+        });
+
+    let mut decode_result = DecodeDiagnostics::new();
+    for (segment_index, scan) in scans.iter().enumerate() {
+        if scan.result.bytes_decoded != decoded_length / segment_count {
+            decode_result.incomplete_decode = true;
+        }
+        decode_result.decode_segment_results[segment_index] = Some(scan.result);
+    }
+    decode_result
+}
+
+/// Decodes a DICOM RLE Image.  When the rayon feature is enabled, images with
+/// more than one segment and at least `PARALLEL_DECODE_THRESHOLD` decoded
+/// bytes have their segments decoded in parallel.
+///
+/// # Arguments
+///
+/// * `encoded`   - The encoded RLE image
+///
+/// * `decoded`   - The decoded buffer, presized to the expected image size
+///
+#[allow(dead_code)]
+pub fn decode(encoded: &[u8], decoded: &mut [u8]) -> Result<DecodeDiagnostics, Error> {
+    let segments = get_segments(encoded)?;
+    Ok(decode_segments_any(&segments, decoded))
+}
+
+// decodes the segments in parallel if the image is large enough and the
+// rayon feature is enabled, otherwise serially
+fn decode_segments_any<T: DecodedByte>(segments: &[&[u8]], decoded: &mut [T]) -> DecodeDiagnostics {
+    #[cfg(feature = "rayon")]
+    {
+        if segments.len() > 1 && decoded.len() >= PARALLEL_DECODE_THRESHOLD {
+            return decode_segments_parallel(segments, decoded);
+        }
+    }
+
+    decode_segments(segments, decoded)
+}
+
+/// Decodes a DICOM RLE Image using the given strategy.  The decoded buffer and
+/// diagnostics are identical for every strategy.
+///
+/// # Arguments
+///
+/// * `encoded`   - The encoded RLE image
+///
+/// * `decoded`   - The decoded buffer, presized to the expected image size
+///
+/// * `strategy`  - How the segments are decoded into the decoded buffer
+///
+pub fn decode_with_strategy(
+    encoded: &[u8],
+    decoded: &mut [u8],
+    strategy: DecodeStrategy,
+) -> Result<DecodeDiagnostics, Error> {
+    let segments = get_segments(encoded)?;
+
+    Ok(match strategy {
+        DecodeStrategy::Strided => decode_segments(&segments, decoded),
+        DecodeStrategy::Planar => decode_segments_planar(&segments, decoded),
+        DecodeStrategy::RowBlocked { columns } => {
+            decode_segments_row_blocked(&segments, decoded, columns)
+        }
+    })
+}
+
+/// Decodes a DICOM RLE Image, decoding the segments in parallel regardless of
+/// the image size.  The decoded buffer and diagnostics are identical to those
+/// produced by `decode()`.
+///
+/// # Arguments
+///
+/// * `encoded`   - The encoded RLE image
+///
+/// * `decoded`   - The decoded buffer, presized to the expected image size
+///
+#[cfg(feature = "rayon")]
+pub fn decode_parallel(encoded: &[u8], decoded: &mut [u8]) -> Result<DecodeDiagnostics, Error> {
+    let segments = get_segments(encoded)?;
+    Ok(decode_segments_parallel(&segments, decoded))
 }
 
 pub fn decode_u16(encoded: &[u8], decoded: &mut [u16]) -> Result<DecodeDiagnostics, Error> {
-    let mut decoded_u8 = unsafe {
+    let decoded_u8 = unsafe {
         let ptr = decoded.as_mut_ptr() as *mut u8;
         slice::from_raw_parts_mut(ptr, decoded.len() * 2)
     };
-    decode(encoded, &mut decoded_u8)
+    decode(encoded, decoded_u8)
 }
 
 pub fn decode_i16(encoded: &[u8], decoded: &mut [i16]) -> Result<DecodeDiagnostics, Error> {
-    let mut decoded_u8 = unsafe {
+    let decoded_u8 = unsafe {
         let ptr = decoded.as_mut_ptr() as *mut u8;
         slice::from_raw_parts_mut(ptr, decoded.len() * 2)
     };
-    decode(encoded, &mut decoded_u8)
+    decode(encoded, decoded_u8)
+}
+
+/// Decodes a DICOM RLE Image into a buffer that does not need to be
+/// initialized first.  Returns the prefix of the decoded buffer that is known
+/// to be initialized along with the diagnostics.  The prefix is the whole
+/// buffer when the decode is complete, otherwise it ends at the first byte
+/// that no segment wrote, so uninitialized bytes are never exposed.
+///
+/// # Arguments
+///
+/// * `encoded`   - The encoded RLE image
+///
+/// * `decoded`   - The decoded buffer, presized to the expected image size
+///
+pub fn decode_uninit<'a>(
+    encoded: &[u8],
+    decoded: &'a mut [MaybeUninit<u8>],
+) -> Result<(&'a mut [u8], DecodeDiagnostics), Error> {
+    let segments = get_segments(encoded)?;
+    let diagnostics = decode_segments_any(&segments, decoded);
+    let initialized = initialized_length(&diagnostics, decoded.len());
+    let initialized = &mut decoded[..initialized];
+    // each segment writes its samples from its start index onwards with no
+    // gaps and reports how many it wrote, so initialized_length() only counts
+    // bytes that were written.  MaybeUninit<u8> has the same layout as u8.
+    let initialized = unsafe {
+        slice::from_raw_parts_mut(initialized.as_mut_ptr() as *mut u8, initialized.len())
+    };
+    Ok((initialized, diagnostics))
+}
+
+/// Decodes a DICOM RLE Image into the spare capacity of a vector, reserving
+/// `decoded_length` bytes first so the vector never has to be zero filled.
+/// The length of the vector grows by the number of bytes that were
+/// initialized, which is `decoded_length` unless the decode is incomplete.
+///
+/// # Arguments
+///
+/// * `encoded`        - The encoded RLE image
+///
+/// * `decoded`        - The vector the decoded image is appended to
+///
+/// * `decoded_length` - The expected image size
+///
+pub fn decode_into_vec(
+    encoded: &[u8],
+    decoded: &mut Vec<u8>,
+    decoded_length: usize,
+) -> Result<DecodeDiagnostics, Error> {
+    decoded.reserve(decoded_length);
+    let spare = &mut decoded.spare_capacity_mut()[..decoded_length];
+    let (initialized, diagnostics) = decode_uninit(encoded, spare)?;
+    let initialized = initialized.len();
+    let length = decoded.len() + initialized;
+    // decode_uninit() initialized this many bytes at the start of the
+    // spare capacity, which is within the capacity reserved above
+    unsafe { decoded.set_len(length) };
+    Ok(diagnostics)
+}
+
+// returns the number of bytes at the start of a decoded buffer of
+// decoded_length bytes that every segment wrote, given the number of values
+// each segment decoded
+fn initialized_length(diagnostics: &DecodeDiagnostics, decoded_length: usize) -> usize {
+    let results: Vec<usize> = diagnostics
+        .decode_segment_results
+        .iter()
+        .map_while(|result| result.map(|result| result.bytes_decoded))
+        .collect();
+    let segment_count = results.len();
+    if segment_count == 0 {
+        return 0;
+    }
+
+    // every pixel before the shortest segment ends is complete, then the
+    // bytes of the next pixel are initialized up to the first segment that
+    // ended there
+    let complete_pixels = results.iter().copied().min().unwrap_or(0);
+    let mut length = complete_pixels.saturating_mul(segment_count);
+    let mut values_by_index = vec![0; segment_count];
+    for (segment_index, bytes_decoded) in results.iter().enumerate() {
+        values_by_index[calculate_start_index(segment_count, segment_index)] = *bytes_decoded;
+    }
+    for bytes_decoded in values_by_index {
+        if bytes_decoded <= complete_pixels {
+            break;
+        }
+        length = length.saturating_add(1);
+    }
+    length.min(decoded_length)
+}
+
+// returns an error if the number of segments in the header does not match
+// the image descriptor
+pub(crate) fn check_segment_count(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(), Error> {
+    let segment_count = read_header(encoded)?.len();
+    if segment_count != descriptor.segment_count() {
+        return Err(Error::Format(format!(
+            "expected {} segments but found {}",
+            descriptor.segment_count(),
+            segment_count
+        )));
+    }
+    Ok(())
+}
+
+/// Decodes a DICOM RLE Image into a new buffer sized using the image
+/// descriptor.  Returns an error if the number of segments in the header does
+/// not match the descriptor.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+pub fn decode_frame(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(Vec<u8>, DecodeDiagnostics), Error> {
+    decode_frame_with_limits(encoded, descriptor, &DecodeLimits::unlimited())
+}
+
+/// Decodes a DICOM RLE Image into a new buffer sized using the image
+/// descriptor, after checking the descriptor and the size of the encoded image
+/// against the limits.  Returns `Error::LimitExceeded` without allocating if a
+/// limit is exceeded.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `limits`     - The limits to enforce
+///
+pub fn decode_frame_with_limits(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+) -> Result<(Vec<u8>, DecodeDiagnostics), Error> {
+    let decoded_length = check_limits(encoded, descriptor, limits)?;
+    check_segment_count(encoded, descriptor)?;
+
+    let mut decoded = vec![0; decoded_length];
+    let diagnostics = decode(encoded, &mut decoded)?;
+
+    Ok((decoded, diagnostics))
+}
+
+// returns an error if the encoded image or the descriptor exceed the limits or
+// the decoded image is too large to allocate, otherwise the decoded length
+fn check_limits(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+) -> Result<usize, Error> {
+    limits.check_encoded(encoded)?;
+    limits.check_descriptor(descriptor)?;
+    checked_decoded_length(descriptor)
+}
+
+// returns the decoded length, or an error if it is too large to allocate
+pub(crate) fn checked_decoded_length(descriptor: &ImageDescriptor) -> Result<usize, Error> {
+    descriptor.checked_decoded_length().ok_or_else(|| {
+        Error::Format(format!(
+            "{} x {} x {} image with {} bits allocated is too large to decode",
+            descriptor.rows,
+            descriptor.columns,
+            descriptor.samples_per_pixel,
+            descriptor.bits_allocated
+        ))
+    })
+}
+
+// returns the number of 16 bit samples in a 16 bit grayscale image, or an
+// error if it is not one.  The segments are written in the byte order of a
+// two segment image, so the samples of color images would come back with
+// their bytes swapped.
+pub(crate) fn check_sixteen_bit_frame(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+) -> Result<usize, Error> {
+    let decoded_length = check_limits(encoded, descriptor, limits)?;
+    check_segment_count(encoded, descriptor)?;
+
+    if descriptor.bits_allocated != 16 || descriptor.samples_per_pixel != 1 {
+        return Err(Error::Format(format!(
+            "expected 16 bits allocated with 1 sample per pixel but found {} bits allocated with {} samples per pixel",
+            descriptor.bits_allocated, descriptor.samples_per_pixel
+        )));
+    }
+
+    Ok(decoded_length / 2)
+}
+
+// decodes a 16 bit grayscale image into a new buffer of 16 bit samples
+fn decode_sixteen_bit_frame<T: Copy + Default>(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+    decode: fn(&[u8], &mut [T]) -> Result<DecodeDiagnostics, Error>,
+) -> Result<(Vec<T>, DecodeDiagnostics), Error> {
+    let samples = check_sixteen_bit_frame(encoded, descriptor, limits)?;
+    let mut decoded = vec![T::default(); samples];
+    let diagnostics = decode(encoded, &mut decoded)?;
+    Ok((decoded, diagnostics))
+}
+
+/// Decodes a 16 bit unsigned DICOM RLE Image into a new buffer sized using the
+/// image descriptor.  Returns an error if the descriptor is not 16 bits
+/// allocated with 1 sample per pixel or the number of segments does not
+/// match.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+pub fn decode_frame_u16(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(Vec<u16>, DecodeDiagnostics), Error> {
+    decode_frame_u16_with_limits(encoded, descriptor, &DecodeLimits::unlimited())
+}
+
+/// Same as `decode_frame_u16()` but first checks the descriptor and the size of
+/// the encoded image against the limits.  Returns `Error::LimitExceeded`
+/// without allocating if a limit is exceeded.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `limits`     - The limits to enforce
+///
+pub fn decode_frame_u16_with_limits(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+) -> Result<(Vec<u16>, DecodeDiagnostics), Error> {
+    decode_sixteen_bit_frame(encoded, descriptor, limits, decode_u16)
+}
+
+/// Decodes a 16 bit signed DICOM RLE Image into a new buffer sized using the
+/// image descriptor.  Returns an error if the descriptor is not 16 bits
+/// allocated with 1 sample per pixel or the number of segments does not
+/// match.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+pub fn decode_frame_i16(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(Vec<i16>, DecodeDiagnostics), Error> {
+    decode_frame_i16_with_limits(encoded, descriptor, &DecodeLimits::unlimited())
+}
+
+/// Same as `decode_frame_i16()` but first checks the descriptor and the size of
+/// the encoded image against the limits.  Returns `Error::LimitExceeded`
+/// without allocating if a limit is exceeded.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `limits`     - The limits to enforce
+///
+pub fn decode_frame_i16_with_limits(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    limits: &DecodeLimits,
+) -> Result<(Vec<i16>, DecodeDiagnostics), Error> {
+    decode_sixteen_bit_frame(encoded, descriptor, limits, decode_i16)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "rayon")]
+    use super::decode_segments_parallel;
+    use super::{decode, decode_into_vec, decode_uninit};
+    use super::{decode_segments, decode_segments_planar, decode_segments_row_blocked};
+    use crate::descriptor::ImageDescriptor;
+    use crate::diagnostics::DecodeDiagnostics;
+    use crate::error::Error;
+    use crate::get_segments::get_segments;
+    use crate::limits::{DecodeLimits, Limit};
+    use crate::test::tests::{make_header, make_image};
+    use std::fs;
+    use std::mem::MaybeUninit;
+
+    type DecodeSegments = fn(&[&[u8]], &mut [u8]) -> DecodeDiagnostics;
+
+    // the paths that must produce the same result as decode_segments()
+    fn paths() -> Vec<DecodeSegments> {
+        #[allow(unused_mut)]
+        let mut paths: Vec<DecodeSegments> = vec![decode_segments_planar];
+        #[cfg(feature = "rayon")]
+        paths.push(decode_segments_parallel);
+        paths
+    }
+
+    // decodes with each path into buffers prefilled with a marker value and
+    // checks that the buffers and diagnostics are identical
+    fn assert_paths_match(encoded: &[u8], decoded_length: usize, columns: usize) {
+        let segments = get_segments(encoded).unwrap();
+        let mut serial = vec![0xa5; decoded_length];
+        let serial_result = decode_segments(&segments, &mut serial);
+
+        for path in paths() {
+            let mut decoded = vec![0xa5; decoded_length];
+            let result = path(&segments, &mut decoded);
+            assert_eq!(serial_result, result);
+            assert!(serial == decoded);
+        }
+
+        let mut decoded = vec![0xa5; decoded_length];
+        let result = decode_segments_row_blocked(&segments, &mut decoded, columns);
+        assert_eq!(serial_result, result);
+        assert!(serial == decoded);
+    }
+
+    #[test]
+    fn paths_match_serial_for_test_images() {
+        for (name, decoded_length, columns) in &[
+            ("ct", 512 * 512 * 2, 512),
+            ("ct1", 512 * 512 * 2, 512),
+            ("ct2", 512 * 512 * 2, 512),
+            ("rf1", 512 * 512, 512),
+            ("us1", 640 * 480 * 3, 640),
+        ] {
+            let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+            assert_paths_match(&encoded, *decoded_length, *columns);
+        }
+    }
+
+    #[test]
+    fn paths_match_serial_for_truncated_images() {
+        for (name, decoded_length, columns) in
+            &[("ct", 512 * 512 * 2, 512), ("us1", 640 * 480 * 3, 640)]
+        {
+            let encoded = fs::read(format!("tests/rleimage/{}.rle", name)).unwrap();
+            for divisor in 2..8 {
+                let truncated = &encoded[..encoded.len() / divisor];
+                assert_paths_match(truncated, *decoded_length, *columns);
+            }
+        }
+    }
+
+    #[test]
+    fn paths_match_serial_for_short_buffers() {
+        // the last pixel is only partly in the buffer and the segments
+        // overflow part way through a chunk of the parallel decode
+        let encoded = fs::read("tests/rleimage/us1.rle").unwrap();
+        for decoded_length in &[640 * 480 * 3 - 1, 200_001, 65_536 * 3 + 2] {
+            assert_paths_match(&encoded, *decoded_length, 640);
+        }
+    }
+
+    #[test]
+    fn paths_match_serial_for_overflow_and_odd_length() {
+        // the msb segment ends with a literal run at the last sample and the
+        // lsb segment overflows the decoded buffer
+        let mut encoded = make_header(&mut vec![2, 64, 67]);
+        encoded.extend_from_slice(&[1, 1, 2, 254, 3]);
+        assert_paths_match(&encoded, 4, 2);
+        assert_paths_match(&encoded, 4, 1);
+        assert_paths_match(&encoded, 5, 2);
+    }
+
+    #[test]
+    fn decode_frame_with_limits() {
+        let encoded = fs::read("tests/rleimage/ct.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 16);
+        let limits = DecodeLimits {
+            max_decoded_bytes: 512 * 512 * 2,
+            max_encoded_bytes: encoded.len(),
+            ..DecodeLimits::default()
+        };
+        let (decoded, _) = super::decode_frame_with_limits(&encoded, &descriptor, &limits).unwrap();
+        let (decoded_u16, _) =
+            super::decode_frame_u16_with_limits(&encoded, &descriptor, &limits).unwrap();
+        assert_eq!(512 * 512 * 2, decoded.len());
+        assert_eq!(512 * 512, decoded_u16.len());
+
+        let limits = DecodeLimits {
+            max_decoded_bytes: 512 * 512 * 2 - 1,
+            ..limits
+        };
+        match super::decode_frame_i16_with_limits(&encoded, &descriptor, &limits) {
+            Err(Error::LimitExceeded { limit, .. }) => assert_eq!(Limit::DecodedBytes, limit),
+            _ => panic!("expected the decoded bytes limit to be exceeded"),
+        }
+
+        let limits = DecodeLimits {
+            max_encoded_bytes: encoded.len() - 1,
+            ..DecodeLimits::default()
+        };
+        match super::decode_frame_with_limits(&encoded, &descriptor, &limits) {
+            Err(Error::LimitExceeded { limit, .. }) => assert_eq!(Limit::EncodedBytes, limit),
+            _ => panic!("expected the encoded bytes limit to be exceeded"),
+        }
+    }
+
+    #[test]
+    fn decode_frame_u16_of_color_image_is_an_error() {
+        let mut encoded = make_header(&mut vec![6, 64, 65, 66, 67, 68, 69]);
+        encoded.extend_from_slice(&[0; 6]);
+        let descriptor = ImageDescriptor::new(1, 1, 3, 16);
+
+        let result = super::decode_frame_u16(&encoded, &descriptor);
+        assert!(matches!(result, Err(Error::Format(_))));
+        let result = super::decode_frame_i16(&encoded, &descriptor);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn decode_with_offsets_near_u32_max() {
+        let mut encoded = make_header(&mut vec![2, 64, 0xffff_ffff]);
+        encoded.extend_from_slice(&[255, 1]);
+        let mut decoded = vec![0; 4];
+
+        let result = super::decode(&encoded, &mut decoded).unwrap();
+        assert!(result.incomplete_decode);
+        assert_eq!(vec![0, 1, 0, 1], decoded);
+    }
+
+    #[test]
+    fn decode_frame_too_large_to_allocate() {
+        let encoded = make_header(&mut vec![2, 64, 64]);
+        let descriptor = ImageDescriptor::new(usize::MAX / 2, 2, 1, 16);
+        assert!(matches!(
+            super::decode_frame(&encoded, &descriptor),
+            Err(Error::Format(_))
+        ));
+    }
+
+    // the largest image DICOM allows overflows a 32 bit usize
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn decode_frame_larger_than_address_space() {
+        let encoded = make_header(&mut vec![6, 64, 64, 64, 64, 64, 64]);
+        let descriptor = ImageDescriptor::new(65535, 65535, 3, 16);
+        assert!(matches!(
+            super::decode_frame(&encoded, &descriptor),
+            Err(Error::Format(_))
+        ));
+    }
+
+    #[test]
+    fn decoded_shorter_than_segment_count() {
+        let mut encoded = make_header(&mut vec![3, 64, 66, 68]);
+        encoded.extend_from_slice(&[0, 1, 0, 2, 0, 3]);
+        for decoded_length in 0..3 {
+            let segments = get_segments(&encoded).unwrap();
+            let mut decoded = vec![0; decoded_length];
+            let result = decode_segments(&segments, &mut decoded);
+            for segment_index in decoded_length..3 {
+                let segment_result = result.decode_segment_results[segment_index].unwrap();
+                assert!(segment_result.decoded_overflow);
+            }
+            assert_paths_match(&encoded, decoded_length, 1);
+        }
+    }
+
+    #[test]
+    fn row_blocked_with_overflowing_row_length() {
+        let mut encoded = make_header(&mut vec![2, 64, 66]);
+        encoded.extend_from_slice(&[255, 1, 255, 2]);
+        let segments = get_segments(&encoded).unwrap();
+        let mut expected = vec![0; 4];
+        let mut decoded = vec![0; 4];
+        let expected_result = decode_segments(&segments, &mut expected);
+        let result = decode_segments_row_blocked(&segments, &mut decoded, usize::MAX);
+        assert_eq!(expected_result, result);
+        assert_eq!(expected, decoded);
+    }
+
+    #[test]
+    fn row_blocked_falls_back_when_run_crosses_rows() {
+        // a single row fills a block so the two pixel run that starts at the
+        // last pixel of the first row crosses into the next block
+        let columns = 64 * 1024;
+        let mut segment = Vec::new();
+        for _ in 0..511 {
+            segment.extend_from_slice(&[129, 1]);
+        }
+        segment.extend_from_slice(&[130, 2, 255, 3]);
+        for _ in 0..511 {
+            segment.extend_from_slice(&[129, 4]);
+        }
+        segment.extend_from_slice(&[130, 5]);
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&segment);
+
+        assert_paths_match(&encoded, columns * 2, columns);
+
+        let segments = get_segments(&encoded).unwrap();
+        let mut decoded = vec![0; columns * 2];
+        let result = decode_segments_row_blocked(&segments, &mut decoded, columns);
+        assert!(!result.incomplete_decode);
+        assert_eq!([2, 3, 3, 4], decoded[columns - 2..columns + 2]);
+    }
+
+    // 1 row of 3 columns of 16 bit values with the given segments
+    fn make_sixteen_bit_image(high: &[u8], low: &[u8]) -> Vec<u8> {
+        let mut encoded = make_header(&mut vec![2, 64, 64 + high.len() as u32]);
+        encoded.extend_from_slice(high);
+        encoded.extend_from_slice(low);
+        encoded
+    }
+
+    #[test]
+    fn decode_uninit_complete() {
+        let encoded = make_sixteen_bit_image(&[2, 1, 2, 3], &[2, 4, 5, 6]);
+        let mut decoded = [MaybeUninit::uninit(); 6];
+        let (initialized, diagnostics) = decode_uninit(&encoded, &mut decoded).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!([4, 1, 5, 2, 6, 3], initialized);
+    }
+
+    #[test]
+    fn decode_uninit_reports_initialized_prefix() {
+        // the low byte of only the first pixel
+        let encoded = make_sixteen_bit_image(&[2, 1, 2, 3], &[0, 4]);
+        let mut decoded = [MaybeUninit::uninit(); 6];
+        let (initialized, diagnostics) = decode_uninit(&encoded, &mut decoded).unwrap();
+        assert!(diagnostics.incomplete_decode);
+        assert_eq!([4, 1], initialized);
+
+        // the high byte of only the first pixel, the low byte of the second
+        // pixel comes first so is initialized too
+        let encoded = make_sixteen_bit_image(&[0, 1], &[2, 4, 5, 6]);
+        let mut decoded = [MaybeUninit::uninit(); 6];
+        let (initialized, _) = decode_uninit(&encoded, &mut decoded).unwrap();
+        assert_eq!([4, 1, 5], initialized);
+    }
+
+    #[test]
+    fn decode_uninit_only_exposes_written_bytes() {
+        // reading an uninitialized byte through the returned slice is
+        // reported by `cargo +nightly miri test --lib decode_uninit`
+        let segments: [&[u8]; 4] = [
+            &[3, 1, 2, 3, 4],
+            &[254, 5],
+            &[1, 6, 7, 253, 8],
+            &[3, 9, 10, 11, 12],
+        ];
+        for segment_count in 1..=segments.len() {
+            let encoded = make_image(&segments[..segment_count]);
+            for length in 64..=encoded.len() {
+                let truncated = &encoded[..length];
+                let mut expected = vec![0; 4 * segment_count];
+                decode(truncated, &mut expected).unwrap();
+
+                let mut decoded = vec![MaybeUninit::uninit(); expected.len()];
+                let (initialized, _) = decode_uninit(truncated, &mut decoded).unwrap();
+                assert_eq!(&expected[..initialized.len()], initialized);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_into_vec_appends() {
+        let encoded = make_sixteen_bit_image(&[2, 1, 2, 3], &[2, 4, 5, 6]);
+        let mut decoded = vec![9];
+        let diagnostics = decode_into_vec(&encoded, &mut decoded, 6).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![9, 4, 1, 5, 2, 6, 3], decoded);
+
+        let encoded = make_sixteen_bit_image(&[2, 1, 2, 3], &[0, 4]);
+        let mut decoded = Vec::new();
+        decode_into_vec(&encoded, &mut decoded, 6).unwrap();
+        assert_eq!(vec![4, 1], decoded);
+    }
 }
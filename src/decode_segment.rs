@@ -1,135 +1,290 @@
-// the result of decoding a single rle segment
-#[derive(Copy, Clone)]
+use crate::tokens::{tokens, Token};
+use std::mem::MaybeUninit;
+
+/// The result of decoding a single rle segment
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct DecodeSegmentResult {
-    // the number of bytes actually decoded
+    /// the number of bytes actually decoded
     pub bytes_decoded: usize,
 
-    // true if the encoded data included a literal run but did not have
-    // enough bytes in the buffer for the literal run length.  This can
-    // occur if:
-    //  * incomplete buffer stream
-    //  * bug in encoder
-    //  * zero padding on last byte (valid as per DICOM)
+    /// the number of encoded bytes consumed by fully decoded runs.  If decoding
+    /// stopped early, this is the offset of the control byte that could not be
+    /// decoded
+    pub bytes_consumed: usize,
+
+    /// true if the encoded data included a literal run but did not have
+    /// enough bytes in the buffer for the literal run length.  This can
+    /// occur if:
+    ///  * incomplete buffer stream
+    ///  * bug in encoder
+    ///  * zero padding on last byte (valid as per DICOM)
     pub literal_run_underflow: bool,
 
-    // true if the encoded data included a replicated run but did not have
-    // enough bytes in the buffer for the run value.  This can occur if:
-    //  * incomplete buffer stream
-    //  * bug in encoder
+    /// true if the encoded data included a replicated run but did not have
+    /// enough bytes in the buffer for the run value.  This can occur if:
+    ///  * incomplete buffer stream
+    ///  * bug in encoder
     pub replicated_run_underflow: bool,
 
-    // true if the decoding would overflow the decoded buffer.  This can occur if:
-    //  * bug in encoder
-    //  * caller did not allocate big enough buffer for encoded
+    /// true if the decoding would overflow the decoded buffer.  This can occur if:
+    ///  * bug in encoder
+    ///  * caller did not allocate big enough buffer for encoded
     pub decoded_overflow: bool,
 
-    // true if an invalid control byte of value 128 was encountered.  This is illegal
-    // as per the DICOM standard and indicates a bug in the encoder
+    /// true if an invalid control byte of value 128 was encountered.  This is illegal
+    /// as per the DICOM standard and indicates a bug in the encoder
     pub invalid_prefix: bool,
 }
 
-/// Decodes a single DICOM RLE Segment
+impl DecodeSegmentResult {
+    // sets the flag for a token that stops decoding, does nothing for literal
+    // and replicated runs
+    pub(crate) fn record_stop(&mut self, token: Token) {
+        match token {
+            Token::Invalid { .. } => self.invalid_prefix = true,
+            Token::LiteralUnderflow { .. } => self.literal_run_underflow = true,
+            Token::ReplicateUnderflow { .. } => self.replicated_run_underflow = true,
+            Token::Literal { .. } | Token::Replicate { .. } => {}
+        }
+    }
+}
+
+// a byte of a decoded buffer.  Decoding only ever writes to the decoded
+// buffer, so it can be decoded into uninitialized memory without creating a
+// `&mut [u8]` over bytes that are not initialized
+pub(crate) trait DecodedByte: Copy + Send {
+    fn from_value(value: u8) -> Self;
+
+    // copies a literal run into contiguous bytes of the same length
+    fn copy_values(destination: &mut [Self], source: &[u8]);
+}
+
+impl DecodedByte for u8 {
+    fn from_value(value: u8) -> u8 {
+        value
+    }
+
+    fn copy_values(destination: &mut [u8], source: &[u8]) {
+        destination.copy_from_slice(source);
+    }
+}
+
+impl DecodedByte for MaybeUninit<u8> {
+    fn from_value(value: u8) -> MaybeUninit<u8> {
+        MaybeUninit::new(value)
+    }
+
+    fn copy_values(destination: &mut [MaybeUninit<u8>], source: &[u8]) {
+        for (byte, value) in destination.iter_mut().zip(source) {
+            *byte = MaybeUninit::new(*value);
+        }
+    }
+}
+
+/// Decodes a single DICOM RLE Segment.  Increments of 1, 2 and 3 (8 bit
+/// grayscale, 16 bit grayscale and 8 bit color) use fast paths that check the
+/// bounds once per run and copy or fill whole runs.  Other increments use
+/// `decode_segment_generic()`.
 ///
 /// # Arguments
 ///
 /// * `segment`   - The encoded RLE segment
 ///
 /// * `decoded`   - The decoded bytes will be stored here.  Must be presized to
-///                 the expected number of decoded bytes.
+///   the expected number of decoded bytes.
 ///
 /// * `increment` - The number of bytes to increment after each byte is decoded.
-///                 This is usually the number of segments.
+///   This is usually the number of segments.
 pub fn decode_segment(segment: &[u8], decoded: &mut [u8], increment: usize) -> DecodeSegmentResult {
-    let mut segment_index = 0;
+    decode_segment_into(segment, decoded, increment)
+}
+
+// same as decode_segment() for any kind of decoded buffer
+pub(crate) fn decode_segment_into<T: DecodedByte>(
+    segment: &[u8],
+    decoded: &mut [T],
+    increment: usize,
+) -> DecodeSegmentResult {
+    match increment {
+        1 => decode_segment_fixed::<T, 1>(segment, decoded),
+        2 => decode_segment_fixed::<T, 2>(segment, decoded),
+        3 => decode_segment_fixed::<T, 3>(segment, decoded),
+        _ => decode_segment_generic_into(segment, decoded, increment),
+    }
+}
+
+// decodes a segment with an increment known at compile time.  The checks and
+// the resulting diagnostics are the same as decode_segment_generic()
+fn decode_segment_fixed<T: DecodedByte, const INCREMENT: usize>(
+    segment: &[u8],
+    decoded: &mut [T],
+) -> DecodeSegmentResult {
     let mut decoded_index = 0;
+    let mut result = DecodeSegmentResult::default();
 
-    let mut result = DecodeSegmentResult {
-        bytes_decoded: 0,
-        literal_run_underflow: false,
-        replicated_run_underflow: false,
-        decoded_overflow: false,
-        invalid_prefix: false,
-    };
-
-    while segment_index < segment.len() {
-        let control = segment[segment_index];
-        segment_index += 1;
-
-        if control <= 127 {
-            // literal run of values case
-
-            // calculate the literal run length
-            let literal_run_length = (control + 1) as usize;
-
-            // detect if we will read past the end of segment.  This can happen if:
-            //  * incomplete buffer stream
-            //  * bug in encoder
-            //  * zero padding on last byte (valid as per DICOM)
-            if (segment_index + literal_run_length) > segment.len() {
-                result.literal_run_underflow = true;
-                break;
-            }
+    let mut runs = tokens(segment);
+    while let Some(token) = runs.next() {
+        match token {
+            Token::Literal { offset, len } => {
+                let last_index = match last_run_index(decoded_index, len, INCREMENT, decoded.len())
+                {
+                    Some(last_index) => last_index,
+                    None => {
+                        result.decoded_overflow = true;
+                        break;
+                    }
+                };
 
-            // detect if will write past end of decoded buffer. This can happen if:
-            //  * bug in encoder
-            //  * caller did not allocate big enough buffer for encoded
-            if (decoded_index + (literal_run_length * increment)) > decoded.len() {
-                result.decoded_overflow = true;
-                break;
-            }
+                let source = &segment[offset..offset + len];
+                let destination = &mut decoded[decoded_index..=last_index];
+                if INCREMENT == 1 {
+                    T::copy_values(destination, source);
+                } else {
+                    for (byte, value) in destination.iter_mut().step_by(INCREMENT).zip(source) {
+                        *byte = T::from_value(*value);
+                    }
+                }
 
-            // copy run_length run_values to decoded vector
-            for _ in 0..literal_run_length {
-                decoded[decoded_index] = segment[segment_index];
-                decoded_index += increment;
-                segment_index += 1;
+                decoded_index = last_index + INCREMENT;
             }
-        } else if control > 128 {
-            // replicated run of values case
+            Token::Replicate { value, len } => {
+                let last_index = match last_run_index(decoded_index, len, INCREMENT, decoded.len())
+                {
+                    Some(last_index) => last_index,
+                    None => {
+                        result.decoded_overflow = true;
+                        break;
+                    }
+                };
 
-            // calculate the run length
-            let run_length = (0 - control as i8) as usize + 1;
+                let destination = &mut decoded[decoded_index..=last_index];
+                if INCREMENT == 1 {
+                    destination.fill(T::from_value(value));
+                } else {
+                    for byte in destination.iter_mut().step_by(INCREMENT) {
+                        *byte = T::from_value(value);
+                    }
+                }
 
-            // detect if will read past end of segment.  This can happen if:
-            //  * incomplete buffer stream
-            //  * bug in encoder
-            if (segment_index + 1) > segment.len() {
-                result.replicated_run_underflow = true;
+                decoded_index = last_index + INCREMENT;
+            }
+            _ => {
+                result.record_stop(token);
                 break;
             }
-            // detect if will write past end of decoded buffer. This can happen if:
-            //  * bug in encoder
-            //  * caller did not allocate big enough buffer for encoded
-            if (decoded_index + ((run_length - 1) * increment)) >= decoded.len() {
+        }
+
+        result.bytes_consumed = runs.position();
+    }
+
+    result.bytes_decoded = decoded_index / INCREMENT;
+
+    result
+}
+
+/// Decodes a single DICOM RLE Segment one byte at a time with a runtime
+/// increment.  `decode_segment()` uses this for increments that do not have a
+/// fast path.  It is public so the fast paths can be benchmarked against it.
+///
+/// # Arguments
+///
+/// * `segment`   - The encoded RLE segment
+///
+/// * `decoded`   - The decoded bytes will be stored here.  Must be presized to
+///   the expected number of decoded bytes.
+///
+/// * `increment` - The number of bytes to increment after each byte is decoded.
+///   This is usually the number of segments.  An increment of zero leaves
+///   nowhere to put the decoded bytes, so nothing is decoded and
+///   `decoded_overflow` is set if the segment is not empty.
+pub fn decode_segment_generic(
+    segment: &[u8],
+    decoded: &mut [u8],
+    increment: usize,
+) -> DecodeSegmentResult {
+    decode_segment_generic_into(segment, decoded, increment)
+}
+
+// same as decode_segment_generic() for any kind of decoded buffer
+fn decode_segment_generic_into<T: DecodedByte>(
+    segment: &[u8],
+    decoded: &mut [T],
+    increment: usize,
+) -> DecodeSegmentResult {
+    let mut decoded_index = 0;
+    let mut result = DecodeSegmentResult::default();
+
+    if increment == 0 {
+        result.decoded_overflow = !segment.is_empty();
+        return result;
+    }
+
+    let mut runs = tokens(segment);
+    while let Some(token) = runs.next() {
+        // detect if will write past end of decoded buffer. This can happen if:
+        //  * bug in encoder
+        //  * caller did not allocate big enough buffer for encoded
+        let run_length = token.decoded_len();
+        if let Token::Literal { .. } | Token::Replicate { .. } = token {
+            if last_run_index(decoded_index, run_length, increment, decoded.len()).is_none() {
                 result.decoded_overflow = true;
                 break;
             }
+        }
 
-            // get the run value
-            let run_value = segment[segment_index];
-            segment_index += 1;
-
-            // write out the run to decoded buffer
-            for _ in 0..run_length {
-                decoded[decoded_index] = run_value;
-                decoded_index += increment;
+        match token {
+            Token::Literal { offset, len } => {
+                // copy run_length run_values to decoded vector
+                for value in &segment[offset..offset + len] {
+                    decoded[decoded_index] = T::from_value(*value);
+                    decoded_index = decoded_index.saturating_add(increment);
+                }
+            }
+            Token::Replicate { value, len } => {
+                // write out the run to decoded buffer
+                for _ in 0..len {
+                    decoded[decoded_index] = T::from_value(value);
+                    decoded_index = decoded_index.saturating_add(increment);
+                }
+            }
+            _ => {
+                // the segment is truncated or has an invalid control byte
+                result.record_stop(token);
+                break;
             }
-        } else {
-            // a control value of 128 is illegal as per the DICOM standard
-            // http://dicom.nema.org/medical/Dicom/2016e/output/chtml/part05/sect_G.3.html
-            result.invalid_prefix = true;
-            break;
         }
-    }
 
-    result.bytes_decoded = decoded_index / increment;
+        result.bytes_decoded += run_length;
+        result.bytes_consumed = runs.position();
+    }
 
     result
 }
 
+// returns the index of the last byte written by a run starting at
+// decoded_index, or None if the run does not fit in the decoded buffer.  The
+// arithmetic is checked since a large increment could otherwise overflow.
+fn last_run_index(
+    decoded_index: usize,
+    run_length: usize,
+    increment: usize,
+    decoded_length: usize,
+) -> Option<usize> {
+    (run_length - 1)
+        .checked_mul(increment)?
+        .checked_add(decoded_index)
+        .filter(|last_index| *last_index < decoded_length)
+}
+
 #[cfg(test)]
+// the original tests keep their style
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::slow_vector_initialization,
+    clippy::vec_resize_to_zero
+)]
 mod tests {
-    use super::decode_segment;
+    use super::{decode_segment, decode_segment_generic};
 
     fn compare(expected: &[u8], actual: &[u8]) {
         assert_eq!(expected.len(), actual.len());
@@ -145,6 +300,7 @@ mod tests {
         decoded.resize(3, 0);
         let result = decode_segment(&segment, &mut decoded, 1);
         assert_eq!(3, result.bytes_decoded);
+        assert_eq!(4, result.bytes_consumed);
         assert_eq!(false, result.literal_run_underflow);
         assert_eq!(false, result.replicated_run_underflow);
         assert_eq!(false, result.decoded_overflow);
@@ -159,6 +315,7 @@ mod tests {
         decoded.resize(3, 0);
         let result = decode_segment(&segment, &mut decoded, 1);
         assert_eq!(3, result.bytes_decoded);
+        assert_eq!(4, result.bytes_consumed);
         assert_eq!(false, result.literal_run_underflow);
         assert_eq!(false, result.replicated_run_underflow);
         assert_eq!(false, result.decoded_overflow);
@@ -246,6 +403,18 @@ mod tests {
         assert_eq!(false, result.invalid_prefix);
     }
 
+    #[test]
+    fn literal_run_ending_at_last_strided_position() {
+        // the msb segment of a two pixel 16 bit image is decoded into a
+        // buffer that starts at the second byte
+        let segment = vec![1, 5, 6];
+        let mut decoded = vec![0; 3];
+        let result = decode_segment(&segment, &mut decoded, 2);
+        assert_eq!(2, result.bytes_decoded);
+        assert!(!result.decoded_overflow);
+        compare(&[5, 0, 6], &decoded);
+    }
+
     #[test]
     fn decoded_overflow_on_replicated_run() {
         let segment = vec![255, 0];
@@ -259,6 +428,16 @@ mod tests {
         assert_eq!(false, result.invalid_prefix);
     }
 
+    #[test]
+    fn bytes_consumed_stops_at_undecodable_control_byte() {
+        let segment = vec![0, 5, 255];
+        let mut decoded = vec![0; 3];
+        let result = decode_segment(&segment, &mut decoded, 1);
+        assert_eq!(1, result.bytes_decoded);
+        assert_eq!(2, result.bytes_consumed);
+        assert!(result.replicated_run_underflow);
+    }
+
     #[test]
     fn zero_length_segment() {
         let segment = vec![];
@@ -271,4 +450,76 @@ mod tests {
         assert_eq!(false, result.decoded_overflow);
         assert_eq!(false, result.invalid_prefix);
     }
+
+    #[test]
+    fn zero_increment_decodes_nothing() {
+        let mut decoded = vec![0; 4];
+        let result = decode_segment(&[255, 1], &mut decoded, 0);
+        assert_eq!(0, result.bytes_decoded);
+        assert_eq!(0, result.bytes_consumed);
+        assert!(result.decoded_overflow);
+        compare(&[0, 0, 0, 0], &decoded);
+
+        let result = decode_segment(&[], &mut decoded, 0);
+        assert!(!result.decoded_overflow);
+    }
+
+    #[test]
+    fn huge_increment_overflows_without_panicking() {
+        // the first sample fits, the second would be past the end of memory
+        let mut decoded = vec![0; 4];
+        let result = decode_segment(&[0, 1, 255, 2, 0, 3], &mut decoded, usize::MAX);
+        assert_eq!(1, result.bytes_decoded);
+        assert_eq!(2, result.bytes_consumed);
+        assert!(result.decoded_overflow);
+        compare(&[1, 0, 0, 0], &decoded);
+
+        let result = decode_segment(&[0, 1, 0, 2], &mut decoded, usize::MAX);
+        assert_eq!(1, result.bytes_decoded);
+        assert!(result.decoded_overflow);
+    }
+
+    #[test]
+    fn fast_paths_match_generic() {
+        // pseudo random segments of short runs exercise every run type along
+        // with underflows, overflows and invalid prefixes
+        let mut state: u32 = 12345;
+        let mut next = |modulus: u32| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) % modulus
+        };
+        for _ in 0..500 {
+            let mut segment = Vec::new();
+            for _ in 0..next(12) {
+                match next(20) {
+                    0 => segment.push(128),
+                    1..=9 => {
+                        let length = next(8);
+                        segment.push(length as u8);
+                        for _ in 0..=length {
+                            segment.push(next(256) as u8);
+                        }
+                    }
+                    _ => {
+                        segment.push((257 - 2 - next(8)) as u8);
+                        segment.push(next(256) as u8);
+                    }
+                }
+            }
+            // truncate some segments mid run
+            let truncate = next(4) as usize;
+            segment.truncate(segment.len().saturating_sub(truncate));
+
+            for increment in 1..=3 {
+                for length in &[0, 1, 10, 31, 60, 100, 301] {
+                    let mut fast = vec![0; *length];
+                    let mut generic = vec![0; *length];
+                    let fast_result = decode_segment(&segment, &mut fast, increment);
+                    let generic_result = decode_segment_generic(&segment, &mut generic, increment);
+                    assert_eq!(generic_result, fast_result);
+                    compare(&generic, &fast);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,100 @@
+/// Describes the layout of a decoded image using the DICOM Image Pixel
+/// attributes.  Used to size decoded buffers and to check that the number of
+/// segments in an RLE image matches what is expected.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageDescriptor {
+    /// Rows (0028,0010)
+    pub rows: usize,
+
+    /// Columns (0028,0011)
+    pub columns: usize,
+
+    /// Samples per Pixel (0028,0002)
+    pub samples_per_pixel: usize,
+
+    /// Bits Allocated (0028,0100)
+    pub bits_allocated: usize,
+}
+
+impl ImageDescriptor {
+    pub fn new(
+        rows: usize,
+        columns: usize,
+        samples_per_pixel: usize,
+        bits_allocated: usize,
+    ) -> ImageDescriptor {
+        ImageDescriptor {
+            rows,
+            columns,
+            samples_per_pixel,
+            bits_allocated,
+        }
+    }
+
+    /// Returns the number of bytes used to store each sample
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bits_allocated.div_ceil(8)
+    }
+
+    /// Returns the number of segments an RLE image with this layout contains.
+    /// Each byte of each sample is stored in its own segment.
+    pub fn segment_count(&self) -> usize {
+        self.samples_per_pixel * self.bytes_per_sample()
+    }
+
+    /// Returns the number of decoded bytes in each segment
+    pub fn segment_length(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Returns the size of the decoded image in bytes.  Overflows if the image
+    /// is too large, see `checked_decoded_length()`.
+    pub fn decoded_length(&self) -> usize {
+        self.segment_length() * self.segment_count()
+    }
+
+    /// Returns the size of the decoded image in bytes, or None if it is too
+    /// large to allocate.  Rust limits allocations to isize::MAX bytes, which
+    /// an image can exceed on 32 bit targets such as wasm32.
+    pub fn checked_decoded_length(&self) -> Option<usize> {
+        self.rows
+            .checked_mul(self.columns)?
+            .checked_mul(self.samples_per_pixel)?
+            .checked_mul(self.bytes_per_sample())
+            .filter(|length| *length <= isize::MAX as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImageDescriptor;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn sixteen_bit_grayscale() {
+        let descriptor = ImageDescriptor::new(512, 256, 1, 16);
+        assert_eq!(2, descriptor.segment_count());
+        assert_eq!(512 * 256, descriptor.segment_length());
+        assert_eq!(512 * 256 * 2, descriptor.decoded_length());
+    }
+
+    #[test]
+    fn eight_bit_color() {
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        assert_eq!(3, descriptor.segment_count());
+        assert_eq!(640 * 480 * 3, descriptor.decoded_length());
+    }
+
+    #[test]
+    fn checked_decoded_length() {
+        let descriptor = ImageDescriptor::new(65535, 65535, 3, 16);
+        assert_eq!(
+            usize::try_from(65535 * 65535 * 6u64).ok(),
+            descriptor.checked_decoded_length()
+        );
+        let descriptor = ImageDescriptor::new(usize::MAX, 2, 1, 8);
+        assert_eq!(None, descriptor.checked_decoded_length());
+        let descriptor = ImageDescriptor::new(isize::MAX as usize, 1, 1, 16);
+        assert_eq!(None, descriptor.checked_decoded_length());
+    }
+}
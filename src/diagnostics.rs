@@ -1,7 +1,7 @@
 use crate::decode_segment::DecodeSegmentResult;
 
 #[allow(dead_code)]
-#[derive(Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 /// Diagnostic information related to decoding an RLE image
 pub struct DecodeDiagnostics {
     /// true if the decoded buffer was not fully populated during the
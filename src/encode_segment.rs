@@ -0,0 +1,153 @@
+/// Writes a DICOM RLE Segment from a sequence of runs of values.  Adjacent
+/// runs of the same value are merged, runs of three or more values are
+/// written as replicated runs and everything else is collected into literal
+/// runs.  A run of two values is written as a replicated run unless it
+/// follows a literal run, since extending the literal run is no larger.
+#[derive(Clone, Debug, Default)]
+pub struct SegmentWriter {
+    encoded: Vec<u8>,
+    literal: Vec<u8>,
+    value: u8,
+    count: usize,
+}
+
+impl SegmentWriter {
+    pub fn new() -> SegmentWriter {
+        SegmentWriter::default()
+    }
+
+    /// Appends `count` copies of `value` to the segment
+    pub fn push_run(&mut self, value: u8, count: usize) {
+        if count == 0 {
+            return;
+        }
+        if self.count > 0 && self.value != value {
+            self.flush_run();
+        }
+        self.value = value;
+        self.count += count;
+    }
+
+    /// Appends each of `values` to the segment
+    pub fn push_literal(&mut self, values: &[u8]) {
+        for value in values {
+            self.push_run(*value, 1);
+        }
+    }
+
+    /// Ends the current row.  The pending runs are written out so no run
+    /// crosses into the next row, as the DICOM standard requires each row to
+    /// be encoded separately.
+    pub fn end_row(&mut self) {
+        self.flush_run();
+        self.flush_literal();
+    }
+
+    /// Returns the encoded segment, padded with a zero byte if needed to make
+    /// its length even as the DICOM standard requires
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush_run();
+        self.flush_literal();
+        if self.encoded.len() % 2 == 1 {
+            self.encoded.push(0);
+        }
+        self.encoded
+    }
+
+    // writes out the pending run of values
+    fn flush_run(&mut self) {
+        let mut count = self.count;
+        self.count = 0;
+
+        while count >= 3 || (count == 2 && self.literal.is_empty()) {
+            self.flush_literal();
+            let run_length = count.min(128);
+            self.encoded.push((257 - run_length) as u8);
+            self.encoded.push(self.value);
+            count -= run_length;
+        }
+
+        for _ in 0..count {
+            if self.literal.len() == 128 {
+                self.flush_literal();
+            }
+            self.literal.push(self.value);
+        }
+    }
+
+    // writes out the pending literal run
+    fn flush_literal(&mut self) {
+        if self.literal.is_empty() {
+            return;
+        }
+        self.encoded.push((self.literal.len() - 1) as u8);
+        self.encoded.append(&mut self.literal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SegmentWriter;
+    use crate::decode_segment::decode_segment;
+
+    fn write(runs: &[(u8, usize)]) -> Vec<u8> {
+        let mut writer = SegmentWriter::new();
+        for (value, count) in runs {
+            writer.push_run(*value, *count);
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn merges_adjacent_runs() {
+        assert_eq!(vec![253, 7], write(&[(7, 1), (7, 2), (7, 1)]));
+    }
+
+    #[test]
+    fn short_runs_are_literal() {
+        assert_eq!(vec![3, 1, 2, 2, 3, 0], write(&[(1, 1), (2, 2), (3, 1)]));
+        assert_eq!(vec![255, 2, 0, 3], write(&[(2, 2), (3, 1)]));
+    }
+
+    #[test]
+    fn long_runs_are_split() {
+        // 128 + 128 + 3 replicated then a single literal
+        let encoded = write(&[(9, 259), (4, 1)]);
+        assert_eq!(vec![129, 9, 129, 9, 254, 9, 0, 4], encoded);
+
+        // a single value left over from a long run joins the literal run
+        let encoded = write(&[(9, 129), (4, 1)]);
+        assert_eq!(vec![129, 9, 1, 9, 4, 0], encoded);
+    }
+
+    #[test]
+    fn long_literal_runs_are_split() {
+        let values: Vec<u8> = (0..200).map(|value| value as u8).collect();
+        let mut writer = SegmentWriter::new();
+        writer.push_literal(&values);
+        let encoded = writer.finish();
+        assert_eq!(127, encoded[0]);
+        assert_eq!(71, encoded[129]);
+        assert_eq!(202, encoded.len());
+
+        let mut decoded = vec![0; 200];
+        let result = decode_segment(&encoded, &mut decoded, 1);
+        assert_eq!(200, result.bytes_decoded);
+        assert_eq!(values, decoded);
+    }
+
+    #[test]
+    fn runs_end_at_row_end() {
+        let mut writer = SegmentWriter::new();
+        writer.push_run(7, 3);
+        writer.push_run(8, 1);
+        writer.end_row();
+        writer.push_run(8, 3);
+        assert_eq!(vec![254, 7, 0, 8, 254, 8], writer.finish());
+    }
+
+    #[test]
+    fn empty_segment() {
+        assert!(SegmentWriter::new().finish().is_empty());
+    }
+}
@@ -1,7 +1,50 @@
+use crate::limits::Limit;
+use std::fmt;
+
 /// Errors that can occur while decoding an image.
 #[derive(Debug)]
 pub enum Error {
     /// The image is not formatted properly. The string contains detailed information about the
     /// error.
     Format(String),
+
+    /// The DICOM Part 10 file could not be read or does not contain an RLE Lossless image.  The
+    /// string contains detailed information about the error.
+    Part10(String),
+
+    /// The image could not be exported. The string contains detailed information about the error.
+    Export(String),
+
+    /// A value read from the image exceeded one of the `DecodeLimits`.
+    LimitExceeded {
+        /// the limit that was exceeded
+        limit: Limit,
+
+        /// the value that exceeded the limit
+        value: usize,
+
+        /// the maximum allowed value
+        maximum: usize,
+    },
 }
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Format(message) => write!(f, "format error: {}", message),
+            Error::Part10(message) => write!(f, "part 10 error: {}", message),
+            Error::Export(message) => write!(f, "export error: {}", message),
+            Error::LimitExceeded {
+                limit,
+                value,
+                maximum,
+            } => write!(
+                f,
+                "limit exceeded: {} {} is more than the maximum of {}",
+                limit, value, maximum
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
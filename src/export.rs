@@ -0,0 +1,390 @@
+use crate::decode::{checked_decoded_length, decode_frame};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use std::io::Write;
+
+/// A VOI window used to map grayscale data to 8 bits
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Window {
+    /// Window Center (0028,1050)
+    pub center: f64,
+
+    /// Window Width (0028,1051).  Must be at least 1
+    pub width: f64,
+}
+
+/// Options controlling how a decoded image is converted for export
+#[derive(Copy, Clone, Debug)]
+pub struct ExportOptions {
+    /// true if the stored values are signed (Pixel Representation of 1)
+    pub signed: bool,
+
+    /// true if the Photometric Interpretation is MONOCHROME1, in which case
+    /// grayscale output is inverted so that higher values are darker
+    pub monochrome1: bool,
+
+    /// Rescale Slope (0028,1053) applied before windowing
+    pub rescale_slope: f64,
+
+    /// Rescale Intercept (0028,1052) applied before windowing
+    pub rescale_intercept: f64,
+
+    /// The window used to map grayscale data to 8 bits.  If None, 8 bit data is
+    /// written as is and 16 bit data is written as 16 bit grayscale.
+    pub window: Option<Window>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> ExportOptions {
+        ExportOptions {
+            signed: false,
+            monochrome1: false,
+            rescale_slope: 1.0,
+            rescale_intercept: 0.0,
+            window: None,
+        }
+    }
+}
+
+/// An image ready to be written as PNG
+pub struct Rendered {
+    /// the pixel data in PNG byte order (16 bit samples are big endian)
+    pub pixels: Vec<u8>,
+
+    /// the number of samples per pixel, 1 for grayscale or 3 for RGB
+    pub samples_per_pixel: usize,
+
+    /// the number of bits per sample, 8 or 16
+    pub bits_per_sample: usize,
+}
+
+// returns the stored value of each grayscale pixel
+fn stored_values<'a>(
+    decoded: &'a [u8],
+    descriptor: &ImageDescriptor,
+    signed: bool,
+) -> Box<dyn Iterator<Item = i32> + 'a> {
+    if descriptor.bits_allocated == 16 {
+        Box::new(decoded.chunks_exact(2).map(move |bytes| {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            if signed {
+                value as i16 as i32
+            } else {
+                value as i32
+            }
+        }))
+    } else {
+        Box::new(decoded.iter().map(move |value| {
+            if signed {
+                *value as i8 as i32
+            } else {
+                *value as i32
+            }
+        }))
+    }
+}
+
+// applies the linear VOI LUT function from PS3.3 C.11.2.1.2.1
+fn apply_window(value: f64, window: &Window) -> u8 {
+    let width = window.width.max(1.0);
+    let lower = window.center - 0.5 - (width - 1.0) / 2.0;
+    let upper = window.center - 0.5 + (width - 1.0) / 2.0;
+    if value <= lower {
+        0
+    } else if value > upper {
+        255
+    } else {
+        (((value - (window.center - 0.5)) / (width - 1.0) + 0.5) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    }
+}
+
+/// Returns a window spanning the full range of modality values in a decoded
+/// grayscale image.  Useful when the image does not specify a window.
+///
+/// # Arguments
+///
+/// * `decoded`    - The decoded image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `options`    - The export options, the window is ignored
+///
+pub fn full_range_window(
+    decoded: &[u8],
+    descriptor: &ImageDescriptor,
+    options: &ExportOptions,
+) -> Window {
+    let mut min = i32::MAX;
+    let mut max = i32::MIN;
+    for value in stored_values(decoded, descriptor, options.signed) {
+        min = min.min(value);
+        max = max.max(value);
+    }
+    if min > max {
+        return Window {
+            center: 0.0,
+            width: 1.0,
+        };
+    }
+
+    let a = min as f64 * options.rescale_slope + options.rescale_intercept;
+    let b = max as f64 * options.rescale_slope + options.rescale_intercept;
+    let (lower, upper) = if a <= b { (a, b) } else { (b, a) };
+    Window {
+        center: (lower + upper + 1.0) / 2.0,
+        width: (upper - lower + 1.0).max(1.0),
+    }
+}
+
+/// Converts a decoded image into the pixel format written to PNG.  Supports 8
+/// bit grayscale, 16 bit grayscale and 8 bit RGB images.
+///
+/// # Arguments
+///
+/// * `decoded`    - The decoded image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `options`    - Controls how grayscale values are mapped
+///
+pub fn render(
+    decoded: &[u8],
+    descriptor: &ImageDescriptor,
+    options: &ExportOptions,
+) -> Result<Rendered, Error> {
+    let decoded_length = checked_decoded_length(descriptor)?;
+    if decoded.len() != decoded_length {
+        return Err(Error::Export(format!(
+            "expected {} decoded bytes but found {}",
+            decoded_length,
+            decoded.len()
+        )));
+    }
+
+    match (descriptor.samples_per_pixel, descriptor.bits_allocated) {
+        (3, 8) => Ok(Rendered {
+            pixels: decoded.to_vec(),
+            samples_per_pixel: 3,
+            bits_per_sample: 8,
+        }),
+        (1, 8) | (1, 16) => {
+            let values = stored_values(decoded, descriptor, options.signed);
+            let pixels = match options.window {
+                Some(window) => values
+                    .map(|value| {
+                        let modality =
+                            value as f64 * options.rescale_slope + options.rescale_intercept;
+                        let output = apply_window(modality, &window);
+                        if options.monochrome1 {
+                            255 - output
+                        } else {
+                            output
+                        }
+                    })
+                    .collect(),
+                None if descriptor.bits_allocated == 8 => values
+                    .map(|value| {
+                        // offset signed values so that ordering is preserved
+                        let output = if options.signed { value + 128 } else { value } as u8;
+                        if options.monochrome1 {
+                            255 - output
+                        } else {
+                            output
+                        }
+                    })
+                    .collect(),
+                None => values
+                    .flat_map(|value| {
+                        let output = if options.signed { value + 32768 } else { value } as u16;
+                        let output = if options.monochrome1 {
+                            65535 - output
+                        } else {
+                            output
+                        };
+                        output.to_be_bytes().to_vec()
+                    })
+                    .collect(),
+            };
+            let bits_per_sample = if options.window.is_some() {
+                8
+            } else {
+                descriptor.bits_allocated
+            };
+            Ok(Rendered {
+                pixels,
+                samples_per_pixel: 1,
+                bits_per_sample,
+            })
+        }
+        (samples_per_pixel, bits_allocated) => Err(Error::Export(format!(
+            "unsupported image with {} samples per pixel and {} bits allocated",
+            samples_per_pixel, bits_allocated
+        ))),
+    }
+}
+
+/// Writes a decoded image as PNG
+///
+/// # Arguments
+///
+/// * `writer`     - Where the PNG is written
+///
+/// * `decoded`    - The decoded image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `options`    - Controls how grayscale values are mapped
+///
+pub fn write_png<W: Write>(
+    writer: W,
+    decoded: &[u8],
+    descriptor: &ImageDescriptor,
+    options: &ExportOptions,
+) -> Result<(), Error> {
+    let rendered = render(decoded, descriptor, options)?;
+
+    let mut encoder = png::Encoder::new(writer, descriptor.columns as u32, descriptor.rows as u32);
+    encoder.set_color(if rendered.samples_per_pixel == 3 {
+        png::ColorType::Rgb
+    } else {
+        png::ColorType::Grayscale
+    });
+    encoder.set_depth(if rendered.bits_per_sample == 16 {
+        png::BitDepth::Sixteen
+    } else {
+        png::BitDepth::Eight
+    });
+
+    let to_error = |error: png::EncodingError| Error::Export(error.to_string());
+    let mut writer = encoder.write_header().map_err(to_error)?;
+    writer
+        .write_image_data(&rendered.pixels)
+        .map_err(to_error)?;
+    writer.finish().map_err(to_error)
+}
+
+/// Decodes a DICOM RLE Image and writes it as PNG.  The diagnostics are
+/// returned so the caller can detect incomplete decodes.
+///
+/// # Arguments
+///
+/// * `writer`     - Where the PNG is written
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `options`    - Controls how grayscale values are mapped
+///
+pub fn export_png<W: Write>(
+    writer: W,
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    options: &ExportOptions,
+) -> Result<DecodeDiagnostics, Error> {
+    let (decoded, diagnostics) = decode_frame(encoded, descriptor)?;
+    write_png(writer, &decoded, descriptor, options)?;
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_window, full_range_window, render, write_png, ExportOptions, Window};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+
+    #[test]
+    fn window_maps_to_full_range() {
+        let window = Window {
+            center: 40.0,
+            width: 400.0,
+        };
+        assert_eq!(0, apply_window(-160.0, &window));
+        assert_eq!(255, apply_window(240.0, &window));
+        assert_eq!(128, apply_window(40.0, &window));
+    }
+
+    #[test]
+    fn sixteen_bit_with_window_rescale_and_inversion() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let decoded = [0x00, 0x00, 0x00, 0x04, 0xff, 0xff];
+        let options = ExportOptions {
+            signed: true,
+            monochrome1: true,
+            rescale_slope: 1.0,
+            rescale_intercept: -1024.0,
+            window: Some(Window {
+                center: 0.0,
+                width: 100.0,
+            }),
+        };
+
+        let rendered = render(&decoded, &descriptor, &options).unwrap();
+        assert_eq!(8, rendered.bits_per_sample);
+        // -1024 is below the window, 0 is at the center, -1025 is below
+        assert_eq!(vec![255, 126, 255], rendered.pixels);
+    }
+
+    #[test]
+    fn sixteen_bit_without_window_is_big_endian() {
+        let descriptor = ImageDescriptor::new(1, 2, 1, 16);
+        let decoded = [0x34, 0x12, 0xff, 0xff];
+
+        let rendered = render(&decoded, &descriptor, &ExportOptions::default()).unwrap();
+        assert_eq!(16, rendered.bits_per_sample);
+        assert_eq!(vec![0x12, 0x34, 0xff, 0xff], rendered.pixels);
+    }
+
+    #[test]
+    fn full_range_window_covers_rescaled_values() {
+        let descriptor = ImageDescriptor::new(1, 2, 1, 8);
+        let options = ExportOptions {
+            rescale_slope: 2.0,
+            rescale_intercept: 10.0,
+            ..Default::default()
+        };
+
+        let window = full_range_window(&[0, 100], &descriptor, &options);
+        let rendered = render(
+            &[0, 100],
+            &descriptor,
+            &ExportOptions {
+                window: Some(window),
+                ..options
+            },
+        )
+        .unwrap();
+        assert_eq!(vec![0, 255], rendered.pixels);
+    }
+
+    #[test]
+    fn writes_rgb_png() {
+        let descriptor = ImageDescriptor::new(1, 2, 3, 8);
+        let mut png = Vec::new();
+        write_png(
+            &mut png,
+            &[1, 2, 3, 4, 5, 6],
+            &descriptor,
+            &ExportOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(b"\x89PNG", &png[0..4]);
+    }
+
+    #[test]
+    fn sixteen_bit_color_is_an_error() {
+        let descriptor = ImageDescriptor::new(1, 1, 3, 16);
+        let result = render(&[0; 6], &descriptor, &ExportOptions::default());
+        assert!(matches!(result, Err(Error::Export(_))));
+    }
+
+    #[test]
+    fn overflowing_image_size_is_an_error() {
+        let descriptor = ImageDescriptor::new(usize::MAX, 2, 3, 8);
+        let result = render(&[0; 6], &descriptor, &ExportOptions::default());
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
@@ -0,0 +1,643 @@
+use crate::decode::{check_sixteen_bit_frame, decode, decode_i16, decode_u16};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::header::read_header;
+use crate::limits::DecodeLimits;
+use crate::stored_bits::{normalize_i16, normalize_u16, StoredBits};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::slice;
+
+/// The maximum number of segments in a DICOM RLE Image
+pub const DICOMRLE_MAX_SEGMENTS: usize = 15;
+
+/// Status codes returned by the C API.  Errors are returned as status codes
+/// rather than allowing panics to cross the FFI boundary.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DicomRleStatus {
+    /// the call succeeded
+    Ok = 0,
+    /// a required pointer argument was null
+    NullPointer = 1,
+    /// the image is not formatted properly
+    FormatError = 2,
+    /// the DICOM Part 10 file could not be read
+    Part10Error = 3,
+    /// the image could not be exported
+    ExportError = 4,
+    /// an unexpected panic was caught, this indicates a bug in the library
+    Panic = 5,
+    /// a value read from the image exceeded a decode limit
+    LimitExceeded = 6,
+}
+
+/// Diagnostic information about a single decoded segment.  See
+/// `DecodeSegmentResult` for a description of each field.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DicomRleSegmentDiagnostics {
+    pub bytes_decoded: usize,
+    pub bytes_consumed: usize,
+    pub literal_run_underflow: bool,
+    pub replicated_run_underflow: bool,
+    pub decoded_overflow: bool,
+    pub invalid_prefix: bool,
+}
+
+/// Diagnostic information related to decoding an RLE image.  Only the first
+/// `segment_count` entries of `segments` are valid.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DicomRleDiagnostics {
+    pub incomplete_decode: bool,
+    pub segment_count: u32,
+    pub segments: [DicomRleSegmentDiagnostics; DICOMRLE_MAX_SEGMENTS],
+}
+
+/// The contents of a DICOM RLE header.  Only the first `segment_count`
+/// entries of `segment_offsets` are valid.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DicomRleHeader {
+    pub segment_count: u32,
+    pub segment_offsets: [u32; DICOMRLE_MAX_SEGMENTS],
+}
+
+/// Describes which bits of each 16 bit sample hold the stored value, see
+/// `StoredBits`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DicomRleStoredBits {
+    /// Bits Stored (0028,0101)
+    pub bits_stored: usize,
+    /// High Bit (0028,0102)
+    pub high_bit: usize,
+    /// Pixel Representation (0028,0103), 0 for unsigned and 1 for signed
+    pub pixel_representation: usize,
+}
+
+fn to_status(error: &Error) -> DicomRleStatus {
+    match error {
+        Error::Format(_) => DicomRleStatus::FormatError,
+        Error::Part10(_) => DicomRleStatus::Part10Error,
+        Error::Export(_) => DicomRleStatus::ExportError,
+        Error::LimitExceeded { .. } => DicomRleStatus::LimitExceeded,
+    }
+}
+
+// runs the body of an exported function, converting panics to a status code
+fn guard<F: FnOnce() -> DicomRleStatus>(body: F) -> DicomRleStatus {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or(DicomRleStatus::Panic)
+}
+
+fn to_c_diagnostics(diagnostics: &DecodeDiagnostics) -> DicomRleDiagnostics {
+    let mut c_diagnostics = DicomRleDiagnostics {
+        incomplete_decode: diagnostics.incomplete_decode,
+        ..Default::default()
+    };
+    for (index, result) in diagnostics.decode_segment_results.iter().enumerate() {
+        if let Some(result) = result {
+            c_diagnostics.segment_count = index as u32 + 1;
+            c_diagnostics.segments[index] = DicomRleSegmentDiagnostics {
+                bytes_decoded: result.bytes_decoded,
+                bytes_consumed: result.bytes_consumed,
+                literal_run_underflow: result.literal_run_underflow,
+                replicated_run_underflow: result.replicated_run_underflow,
+                decoded_overflow: result.decoded_overflow,
+                invalid_prefix: result.invalid_prefix,
+            };
+        }
+    }
+    c_diagnostics
+}
+
+// shared implementation of the typed decode functions
+unsafe fn decode_typed<T>(
+    encoded: *const u8,
+    encoded_length: usize,
+    decoded: *mut T,
+    decoded_length: usize,
+    diagnostics: *mut DicomRleDiagnostics,
+    decode: fn(&[u8], &mut [T]) -> Result<DecodeDiagnostics, Error>,
+) -> DicomRleStatus {
+    if encoded.is_null() || decoded.is_null() {
+        return DicomRleStatus::NullPointer;
+    }
+    let encoded = slice::from_raw_parts(encoded, encoded_length);
+    let decoded = slice::from_raw_parts_mut(decoded, decoded_length);
+
+    match decode(encoded, decoded) {
+        Ok(result) => {
+            if !diagnostics.is_null() {
+                *diagnostics = to_c_diagnostics(&result);
+            }
+            DicomRleStatus::Ok
+        }
+        Err(error) => to_status(&error),
+    }
+}
+
+/// Decodes a DICOM RLE Image.  `diagnostics` may be null if not needed.
+///
+/// # Safety
+///
+/// `encoded` must point to `encoded_length` readable bytes and `decoded` must
+/// point to `decoded_length` writable bytes.  `diagnostics` must be null or
+/// point to a writable `DicomRleDiagnostics`.
+#[no_mangle]
+pub unsafe extern "C" fn dicomrle_decode(
+    encoded: *const u8,
+    encoded_length: usize,
+    decoded: *mut u8,
+    decoded_length: usize,
+    diagnostics: *mut DicomRleDiagnostics,
+) -> DicomRleStatus {
+    guard(|| {
+        decode_typed(
+            encoded,
+            encoded_length,
+            decoded,
+            decoded_length,
+            diagnostics,
+            decode,
+        )
+    })
+}
+
+// shared implementation of the 16 bit decode functions, the image is checked
+// the same way as decode_frame_u16 and decode_frame_i16 check it and the
+// samples are normalized if stored bits are given
+#[allow(clippy::too_many_arguments)]
+unsafe fn decode_sixteen_bit<T>(
+    encoded: *const u8,
+    encoded_length: usize,
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    stored_bits: *const DicomRleStoredBits,
+    decoded: *mut T,
+    decoded_length: usize,
+    diagnostics: *mut DicomRleDiagnostics,
+    decode: fn(&[u8], &mut [T]) -> Result<DecodeDiagnostics, Error>,
+    normalize: fn(&mut [T], &StoredBits) -> Result<(), Error>,
+) -> DicomRleStatus {
+    if encoded.is_null() || decoded.is_null() {
+        return DicomRleStatus::NullPointer;
+    }
+    let descriptor = ImageDescriptor::new(rows, columns, samples_per_pixel, 16);
+    let samples = match check_sixteen_bit_frame(
+        slice::from_raw_parts(encoded, encoded_length),
+        &descriptor,
+        &DecodeLimits::unlimited(),
+    ) {
+        Ok(samples) => samples,
+        Err(error) => return to_status(&error),
+    };
+    if samples != decoded_length {
+        return DicomRleStatus::FormatError;
+    }
+
+    // the stored bits are checked before decoding so an error leaves the
+    // decoded buffer untouched
+    let stored = stored_bits.as_ref().map(|stored_bits| {
+        StoredBits::new(
+            stored_bits.bits_stored,
+            stored_bits.high_bit,
+            stored_bits.pixel_representation,
+        )
+    });
+    if let Some(Err(error)) = stored.as_ref().map(StoredBits::check) {
+        return to_status(&error);
+    }
+
+    let status = decode_typed(
+        encoded,
+        encoded_length,
+        decoded,
+        decoded_length,
+        diagnostics,
+        decode,
+    );
+    match stored {
+        Some(stored) if status == DicomRleStatus::Ok => {
+            let decoded = slice::from_raw_parts_mut(decoded, decoded_length);
+            match normalize(decoded, &stored) {
+                Ok(()) => DicomRleStatus::Ok,
+                Err(error) => to_status(&error),
+            }
+        }
+        _ => status,
+    }
+}
+
+/// Decodes a 16 bit unsigned grayscale DICOM RLE Image.  `decoded_length` is
+/// the number of samples, not bytes, and must equal `rows * columns`.  Images
+/// with more than one sample per pixel are rejected with
+/// `DicomRleStatus::FormatError`.  If `stored_bits` is not null the samples
+/// are normalized like `normalize_u16()`, masking off the bits above High
+/// Bit and sign extending signed values.  `diagnostics` may be null if not
+/// needed.
+///
+/// # Safety
+///
+/// `encoded` must point to `encoded_length` readable bytes and `decoded` must
+/// point to `decoded_length` writable and aligned samples.  `stored_bits` must
+/// be null or point to a readable `DicomRleStoredBits` and `diagnostics` must
+/// be null or point to a writable `DicomRleDiagnostics`.
+#[no_mangle]
+pub unsafe extern "C" fn dicomrle_decode_u16(
+    encoded: *const u8,
+    encoded_length: usize,
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    stored_bits: *const DicomRleStoredBits,
+    decoded: *mut u16,
+    decoded_length: usize,
+    diagnostics: *mut DicomRleDiagnostics,
+) -> DicomRleStatus {
+    guard(|| {
+        decode_sixteen_bit(
+            encoded,
+            encoded_length,
+            rows,
+            columns,
+            samples_per_pixel,
+            stored_bits,
+            decoded,
+            decoded_length,
+            diagnostics,
+            decode_u16,
+            normalize_u16,
+        )
+    })
+}
+
+/// Decodes a 16 bit signed grayscale DICOM RLE Image.  `decoded_length` is
+/// the number of samples, not bytes, and must equal `rows * columns`.  Images
+/// with more than one sample per pixel are rejected with
+/// `DicomRleStatus::FormatError`.  If `stored_bits` is not null the samples
+/// are normalized like `normalize_i16()`, masking off the bits above High
+/// Bit and sign extending signed values.  `diagnostics` may be null if not
+/// needed.
+///
+/// # Safety
+///
+/// `encoded` must point to `encoded_length` readable bytes and `decoded` must
+/// point to `decoded_length` writable and aligned samples.  `stored_bits` must
+/// be null or point to a readable `DicomRleStoredBits` and `diagnostics` must
+/// be null or point to a writable `DicomRleDiagnostics`.
+#[no_mangle]
+pub unsafe extern "C" fn dicomrle_decode_i16(
+    encoded: *const u8,
+    encoded_length: usize,
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    stored_bits: *const DicomRleStoredBits,
+    decoded: *mut i16,
+    decoded_length: usize,
+    diagnostics: *mut DicomRleDiagnostics,
+) -> DicomRleStatus {
+    guard(|| {
+        decode_sixteen_bit(
+            encoded,
+            encoded_length,
+            rows,
+            columns,
+            samples_per_pixel,
+            stored_bits,
+            decoded,
+            decoded_length,
+            diagnostics,
+            decode_i16,
+            normalize_i16,
+        )
+    })
+}
+
+/// Reads the header of a DICOM RLE Image
+///
+/// # Safety
+///
+/// `encoded` must point to `encoded_length` readable bytes and `header` must
+/// point to a writable `DicomRleHeader`.
+#[no_mangle]
+pub unsafe extern "C" fn dicomrle_read_header(
+    encoded: *const u8,
+    encoded_length: usize,
+    header: *mut DicomRleHeader,
+) -> DicomRleStatus {
+    guard(|| {
+        if encoded.is_null() || header.is_null() {
+            return DicomRleStatus::NullPointer;
+        }
+        let encoded = slice::from_raw_parts(encoded, encoded_length);
+
+        match read_header(encoded) {
+            Ok(segment_offsets) => {
+                let mut c_header = DicomRleHeader {
+                    segment_count: segment_offsets.len() as u32,
+                    ..Default::default()
+                };
+                for (index, offset) in segment_offsets.iter().enumerate() {
+                    // offsets are read from a u32 so this cannot truncate
+                    c_header.segment_offsets[index] = *offset as u32;
+                }
+                *header = c_header;
+                DicomRleStatus::Ok
+            }
+            Err(error) => to_status(&error),
+        }
+    })
+}
+
+/// Returns a static, NUL terminated description of a status code.  The status
+/// is taken as an integer since C callers can pass any value, values that are
+/// not a `DicomRleStatus` are described as an unknown status.
+#[no_mangle]
+pub extern "C" fn dicomrle_status_message(status: u32) -> *const c_char {
+    let message: &'static [u8] = match status {
+        0 => b"ok\0",
+        1 => b"a required pointer argument was null\0",
+        2 => b"the image is not formatted properly\0",
+        3 => b"the DICOM Part 10 file could not be read\0",
+        4 => b"the image could not be exported\0",
+        5 => b"unexpected panic\0",
+        6 => b"a value read from the image exceeded a decode limit\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limit;
+    use crate::test::tests::make_header;
+    use std::ffi::CStr;
+    use std::ptr;
+
+    fn make_two_segment_image() -> Vec<u8> {
+        let mut encoded = make_header(&mut vec![2, 64, 66]);
+        encoded.extend_from_slice(&[255, 1, 255, 2]);
+        encoded
+    }
+
+    #[test]
+    fn decode_u16_with_diagnostics() {
+        let encoded = make_two_segment_image();
+        let mut decoded = [0u16; 2];
+        let mut diagnostics = DicomRleDiagnostics::default();
+
+        let status = unsafe {
+            dicomrle_decode_u16(
+                encoded.as_ptr(),
+                encoded.len(),
+                1,
+                2,
+                1,
+                ptr::null(),
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                &mut diagnostics,
+            )
+        };
+        assert_eq!(DicomRleStatus::Ok, status);
+        assert_eq!([0x0102, 0x0102], decoded);
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(2, diagnostics.segment_count);
+        assert_eq!(2, diagnostics.segments[1].bytes_decoded);
+    }
+
+    #[test]
+    fn decode_without_diagnostics() {
+        let encoded = make_two_segment_image();
+        let mut decoded = [0u8; 4];
+
+        let status = unsafe {
+            dicomrle_decode(
+                encoded.as_ptr(),
+                encoded.len(),
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(DicomRleStatus::Ok, status);
+        assert_eq!([2, 1, 2, 1], decoded);
+    }
+
+    #[test]
+    fn decode_invalid_header_returns_format_error() {
+        let encoded = make_header(&mut vec![0]);
+        let mut decoded = [0i16; 1];
+
+        let status = unsafe {
+            dicomrle_decode_i16(
+                encoded.as_ptr(),
+                encoded.len(),
+                1,
+                1,
+                1,
+                ptr::null(),
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(DicomRleStatus::FormatError, status);
+    }
+
+    #[test]
+    fn decode_i16_with_stored_bits() {
+        let encoded = make_two_segment_image();
+        let stored_bits = DicomRleStoredBits {
+            bits_stored: 9,
+            high_bit: 8,
+            pixel_representation: 1,
+        };
+        let mut decoded = [0i16; 2];
+
+        let status = unsafe {
+            dicomrle_decode_i16(
+                encoded.as_ptr(),
+                encoded.len(),
+                1,
+                2,
+                1,
+                &stored_bits,
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(DicomRleStatus::Ok, status);
+        // 0x0102 has bit 8 set, the sign bit of a 9 bit value
+        assert_eq!([-254, -254], decoded);
+    }
+
+    #[test]
+    fn decode_u16_with_invalid_stored_bits_returns_format_error() {
+        let encoded = make_two_segment_image();
+        let stored_bits = DicomRleStoredBits {
+            bits_stored: 12,
+            high_bit: 16,
+            pixel_representation: 0,
+        };
+        let mut decoded = [0u16; 2];
+
+        let status = unsafe {
+            dicomrle_decode_u16(
+                encoded.as_ptr(),
+                encoded.len(),
+                1,
+                2,
+                1,
+                &stored_bits,
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(DicomRleStatus::FormatError, status);
+        assert_eq!([0, 0], decoded);
+    }
+
+    #[test]
+    fn decode_u16_color_image_returns_format_error() {
+        let mut encoded = make_header(&mut vec![6, 64, 66, 68, 70, 72, 74]);
+        encoded.extend_from_slice(&[0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6]);
+        let mut decoded = [0u16; 3];
+
+        let status = unsafe {
+            dicomrle_decode_u16(
+                encoded.as_ptr(),
+                encoded.len(),
+                1,
+                1,
+                3,
+                ptr::null(),
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(DicomRleStatus::FormatError, status);
+        assert_eq!([0, 0, 0], decoded);
+    }
+
+    #[test]
+    fn decode_i16_with_wrong_buffer_size_returns_format_error() {
+        let encoded = make_two_segment_image();
+        let mut decoded = [0i16; 1];
+
+        let status = unsafe {
+            dicomrle_decode_i16(
+                encoded.as_ptr(),
+                encoded.len(),
+                1,
+                2,
+                1,
+                ptr::null(),
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(DicomRleStatus::FormatError, status);
+    }
+
+    #[test]
+    fn decode_null_pointer() {
+        let mut decoded = [0u8; 1];
+        let status =
+            unsafe { dicomrle_decode(ptr::null(), 0, decoded.as_mut_ptr(), 1, ptr::null_mut()) };
+        assert_eq!(DicomRleStatus::NullPointer, status);
+    }
+
+    #[test]
+    fn decode_into_buffer_shorter_than_segment_count() {
+        let encoded = make_two_segment_image();
+        let mut decoded = [0u8; 0];
+        let mut diagnostics = DicomRleDiagnostics::default();
+
+        let status = unsafe {
+            dicomrle_decode(
+                encoded.as_ptr(),
+                encoded.len(),
+                decoded.as_mut_ptr(),
+                decoded.len(),
+                &mut diagnostics,
+            )
+        };
+        assert_eq!(DicomRleStatus::Ok, status);
+        assert!(diagnostics.segments[0].decoded_overflow);
+    }
+
+    #[test]
+    fn errors_map_to_status_codes() {
+        let error = Error::LimitExceeded {
+            limit: Limit::Rows,
+            value: 2,
+            maximum: 1,
+        };
+        assert_eq!(DicomRleStatus::LimitExceeded, to_status(&error));
+        let error = Error::Format("bad".to_owned());
+        assert_eq!(DicomRleStatus::FormatError, to_status(&error));
+    }
+
+    #[test]
+    fn panic_is_caught() {
+        assert_eq!(DicomRleStatus::Panic, guard(|| panic!("unexpected")));
+    }
+
+    #[test]
+    fn read_header() {
+        let encoded = make_two_segment_image();
+        let mut header = DicomRleHeader::default();
+
+        let status = unsafe { dicomrle_read_header(encoded.as_ptr(), encoded.len(), &mut header) };
+        assert_eq!(DicomRleStatus::Ok, status);
+        assert_eq!(2, header.segment_count);
+        assert_eq!([64, 66], header.segment_offsets[0..2]);
+    }
+
+    fn status_message_text(status: u32) -> &'static str {
+        unsafe { CStr::from_ptr(dicomrle_status_message(status)) }
+            .to_str()
+            .unwrap()
+    }
+
+    #[test]
+    fn status_message() {
+        assert_eq!("ok", status_message_text(DicomRleStatus::Ok as u32));
+        assert_eq!(
+            "unexpected panic",
+            status_message_text(DicomRleStatus::Panic as u32)
+        );
+        assert_eq!(
+            "a value read from the image exceeded a decode limit",
+            status_message_text(DicomRleStatus::LimitExceeded as u32)
+        );
+        assert_eq!("unknown status", status_message_text(7));
+        assert_eq!("unknown status", status_message_text(u32::MAX));
+    }
+
+    #[test]
+    fn header_is_up_to_date() {
+        // the build script writes the header generated from this file to
+        // OUT_DIR, set DICOMRLE_UPDATE_HEADER=1 to copy it to include/
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/dicomrle.h"));
+        let committed = concat!(env!("CARGO_MANIFEST_DIR"), "/include/dicomrle.h");
+        if std::env::var_os("DICOMRLE_UPDATE_HEADER").is_some() {
+            std::fs::write(committed, generated).unwrap();
+        }
+        assert!(
+            std::fs::read_to_string(committed).unwrap() == generated,
+            "include/dicomrle.h is out of date, rerun the tests with DICOMRLE_UPDATE_HEADER=1"
+        );
+    }
+}
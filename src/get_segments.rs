@@ -98,4 +98,13 @@ mod tests {
         assert_eq!(segments[0].len(), 2);
         assert_eq!(segments[1].len(), 0);
     }
+
+    #[test]
+    fn offsets_near_u32_max() {
+        let mut encoded = make_header(&mut vec![3, 64, 0xffff_fff0, 0xffff_ffff]);
+        encoded.extend_from_slice(&[255, 1]);
+
+        let segments = get_segments(&encoded).unwrap();
+        assert_eq!(vec![&[255u8, 1][..], &[][..], &[][..]], segments);
+    }
 }
@@ -1,17 +1,22 @@
 use crate::error::Error;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::convert::TryFrom;
 use std::io::Cursor;
 
 // helper function to read usize from the header
-fn read_usize(cursor: &mut Cursor<&&[u8]>) -> usize {
-    // read u32 from the cursor.  Note that the unwrap() is safe here because
-    // we previously validated the length of the header
-    let value = cursor.read_u32::<LittleEndian>().unwrap();
-
-    // convert from u32 to usize.  Note that unwrap() is safe here because
-    // we are not targeting platforms with less than 32 bits
-    usize::try_from(value).unwrap()
+fn read_usize(cursor: &mut Cursor<&&[u8]>) -> Result<usize, Error> {
+    let value = cursor
+        .read_u32::<LittleEndian>()
+        .map_err(|_| Error::Format("unexpected eof reading header".to_owned()))?;
+
+    // usize is at least 32 bits on the supported targets, but convert with a
+    // check rather than assume it
+    usize::try_from(value).map_err(|_| {
+        Error::Format(format!(
+            "invalid header - value {} does not fit in usize",
+            value
+        ))
+    })
 }
 
 /// Parses the DICOM RLE Header and returns the starting offset for each
@@ -36,7 +41,7 @@ pub fn read_header(header_bytes: &[u8]) -> Result<Vec<usize>, Error> {
     let mut reader = Cursor::new(&header_bytes);
 
     // Read the segment count from the beginning of header
-    let segment_count = read_usize(&mut reader);
+    let segment_count = read_usize(&mut reader)?;
 
     // validate number of segments
     if segment_count > 15 {
@@ -53,7 +58,7 @@ pub fn read_header(header_bytes: &[u8]) -> Result<Vec<usize>, Error> {
     // read each segment offset into a vector
     let mut segment_offsets: Vec<usize> = Vec::new();
     for _ in 0..segment_count {
-        let segment_offset = read_usize(&mut reader);
+        let segment_offset = read_usize(&mut reader)?;
         segment_offsets.push(segment_offset);
     }
 
@@ -76,9 +81,46 @@ pub fn read_header(header_bytes: &[u8]) -> Result<Vec<usize>, Error> {
     Ok(segment_offsets)
 }
 
+/// Builds the DICOM RLE Header for segments of the given lengths, which are
+/// stored one after the other following the header.  Returns an error if
+/// there are no segments or more than 15, or the segments are too large for
+/// their offsets to fit in 32 bits.
+///
+/// # Arguments
+///
+/// * `segment_lengths` - The length in bytes of each encoded segment
+///
+pub fn write_header(segment_lengths: &[usize]) -> Result<Vec<u8>, Error> {
+    if segment_lengths.is_empty() || segment_lengths.len() > 15 {
+        return Err(Error::Format(format!(
+            "cannot write a header for {} segments, must be 1..15 inclusive",
+            segment_lengths.len()
+        )));
+    }
+
+    let mut header = Vec::with_capacity(64);
+    header
+        .write_u32::<LittleEndian>(segment_lengths.len() as u32)
+        .unwrap();
+
+    let mut offset: usize = 64;
+    for length in segment_lengths {
+        let value = u32::try_from(offset)
+            .map_err(|_| Error::Format(format!("segment offset {} is too large", offset)))?;
+        header.write_u32::<LittleEndian>(value).unwrap();
+        offset = offset.checked_add(*length).ok_or_else(|| {
+            Error::Format("segments are too large to write a header for".to_owned())
+        })?;
+    }
+    header.resize(64, 0);
+
+    Ok(header)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::read_header;
+    use super::{read_header, write_header};
+    use crate::error::Error;
     use crate::test::tests::make_header;
 
     #[test]
@@ -140,4 +182,18 @@ mod tests {
 
         read_header(&encoded).unwrap();
     }
+
+    #[test]
+    fn write_header_round_trips() {
+        let header = write_header(&[10, 20, 30]).unwrap();
+
+        assert_eq!(header.len(), 64);
+        assert_eq!(read_header(&header).unwrap(), vec![64, 74, 94]);
+        assert_eq!(header, make_header(&mut vec![3, 64, 74, 94]));
+    }
+
+    #[test]
+    fn write_header_with_16_segments_is_an_error() {
+        assert!(matches!(write_header(&[2; 16]), Err(Error::Format(_))));
+    }
 }
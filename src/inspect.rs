@@ -0,0 +1,270 @@
+use crate::decode_segment::DecodeSegmentResult;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::header::read_header;
+use crate::tokens::{tokens, Token};
+
+/// Statistics about the runs contained in a single RLE segment
+#[derive(Clone, Debug)]
+pub struct RunStatistics {
+    /// the number of literal runs
+    pub literal_runs: usize,
+
+    /// the number of decoded bytes produced by literal runs
+    pub literal_bytes: usize,
+
+    /// the number of replicated runs
+    pub replicated_runs: usize,
+
+    /// the number of decoded bytes produced by replicated runs
+    pub replicated_bytes: usize,
+
+    /// histogram of literal run lengths.  Index n holds the number of literal
+    /// runs of length n + 1
+    pub literal_run_lengths: [usize; 128],
+
+    /// histogram of replicated run lengths.  Index n holds the number of
+    /// replicated runs of length n + 1.  Index 0 is always zero since the
+    /// shortest replicated run is 2 bytes long
+    pub replicated_run_lengths: [usize; 128],
+}
+
+impl RunStatistics {
+    pub fn new() -> RunStatistics {
+        RunStatistics {
+            literal_runs: 0,
+            literal_bytes: 0,
+            replicated_runs: 0,
+            replicated_bytes: 0,
+            literal_run_lengths: [0; 128],
+            replicated_run_lengths: [0; 128],
+        }
+    }
+}
+
+impl Default for RunStatistics {
+    fn default() -> RunStatistics {
+        RunStatistics::new()
+    }
+}
+
+/// Inspection results for a single RLE segment
+#[derive(Clone, Debug)]
+pub struct SegmentInspection {
+    /// the offset of the segment as stored in the header
+    pub offset: usize,
+
+    /// the number of encoded bytes available for the segment
+    pub length: usize,
+
+    /// statistics about the runs in the segment
+    pub runs: RunStatistics,
+
+    /// the result of decoding the segment
+    pub decode_result: DecodeSegmentResult,
+}
+
+impl SegmentInspection {
+    /// Returns the ratio of decoded bytes to encoded bytes for this segment,
+    /// or zero if the segment is empty
+    pub fn compression_ratio(&self) -> f64 {
+        if self.length == 0 {
+            return 0.0;
+        }
+        self.decode_result.bytes_decoded as f64 / self.length as f64
+    }
+}
+
+/// Inspection results for a DICOM RLE Image
+#[derive(Clone, Debug)]
+pub struct Inspection {
+    /// the length of the encoded RLE image
+    pub encoded_length: usize,
+
+    /// the segment offsets read from the header
+    pub segment_offsets: Vec<usize>,
+
+    /// inspection results for each segment
+    pub segments: Vec<SegmentInspection>,
+}
+
+/// Walks the control bytes of a segment and collects statistics about its
+/// runs.  Stops at the first run that cannot be decoded.
+///
+/// # Arguments
+///
+/// * `segment`   - The encoded RLE segment
+///
+pub fn run_statistics(segment: &[u8]) -> RunStatistics {
+    let mut statistics = RunStatistics::new();
+    for token in tokens(segment) {
+        match token {
+            Token::Literal { len, .. } => {
+                statistics.literal_runs += 1;
+                statistics.literal_bytes = statistics.literal_bytes.saturating_add(len);
+                statistics.literal_run_lengths[len - 1] += 1;
+            }
+            Token::Replicate { len, .. } => {
+                statistics.replicated_runs += 1;
+                // a large segment of replicated runs can decode to more bytes
+                // than a 32 bit usize holds
+                statistics.replicated_bytes = statistics.replicated_bytes.saturating_add(len);
+                statistics.replicated_run_lengths[len - 1] += 1;
+            }
+            _ => break,
+        }
+    }
+
+    statistics
+}
+
+// returns the result decode_segment() gives for a segment decoded into
+// segment_length bytes, found from the runs without decoding them.  Without a
+// segment length every run fits.
+fn segment_result(segment: &[u8], segment_length: Option<usize>) -> DecodeSegmentResult {
+    let capacity = segment_length.unwrap_or(usize::MAX);
+    let mut result = DecodeSegmentResult::default();
+
+    let mut runs = tokens(segment);
+    while let Some(token) = runs.next() {
+        let len = token.decoded_len();
+        if len == 0 {
+            result.record_stop(token);
+            break;
+        }
+        if len > capacity - result.bytes_decoded {
+            result.decoded_overflow = true;
+            break;
+        }
+        result.bytes_decoded += len;
+        result.bytes_consumed = runs.position();
+    }
+
+    result
+}
+
+/// Inspects a DICOM RLE Image, returning the header offsets along with run
+/// statistics and decode results for each segment.  The decode results are
+/// found from the runs, the segments are never decoded.
+///
+/// # Arguments
+///
+/// * `encoded`        - The encoded RLE image
+///
+/// * `segment_length` - The expected number of decoded bytes per segment.  If
+///   not known, every run of each segment fits.
+///
+pub fn inspect(encoded: &[u8], segment_length: Option<usize>) -> Result<Inspection, Error> {
+    let segment_offsets = read_header(encoded)?;
+    let segments = get_segments(encoded)?;
+
+    let mut inspection = Inspection {
+        encoded_length: encoded.len(),
+        segment_offsets: segment_offsets.clone(),
+        segments: Vec::new(),
+    };
+
+    for (segment, offset) in segments.iter().zip(segment_offsets) {
+        let runs = run_statistics(segment);
+        let decode_result = segment_result(segment, segment_length);
+
+        inspection.segments.push(SegmentInspection {
+            offset,
+            length: segment.len(),
+            runs,
+            decode_result,
+        });
+    }
+
+    Ok(inspection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect, run_statistics, segment_result};
+    use crate::decode_segment::decode_segment;
+    use crate::test::tests::make_header;
+
+    #[test]
+    fn run_statistics_counts_runs() {
+        let segment = vec![1, 7, 8, 253, 9, 0, 10, 255, 11];
+        let statistics = run_statistics(&segment);
+        assert_eq!(2, statistics.literal_runs);
+        assert_eq!(3, statistics.literal_bytes);
+        assert_eq!(2, statistics.replicated_runs);
+        assert_eq!(6, statistics.replicated_bytes);
+        assert_eq!(1, statistics.literal_run_lengths[0]);
+        assert_eq!(1, statistics.literal_run_lengths[1]);
+        assert_eq!(1, statistics.replicated_run_lengths[1]);
+        assert_eq!(1, statistics.replicated_run_lengths[3]);
+    }
+
+    #[test]
+    fn run_statistics_stops_at_invalid_prefix() {
+        let segment = vec![0, 7, 128, 255, 9];
+        let statistics = run_statistics(&segment);
+        assert_eq!(1, statistics.literal_runs);
+        assert_eq!(0, statistics.replicated_runs);
+    }
+
+    #[test]
+    fn inspect_two_segments() {
+        let mut encoded = make_header(&mut vec![2, 64, 67]);
+        encoded.extend_from_slice(&[1, 1, 2, 253, 3]);
+
+        let inspection = inspect(&encoded, Some(4)).unwrap();
+        assert_eq!(69, inspection.encoded_length);
+        assert_eq!(vec![64, 67], inspection.segment_offsets);
+        assert_eq!(2, inspection.segments.len());
+
+        let first = &inspection.segments[0];
+        assert_eq!(3, first.length);
+        assert_eq!(2, first.decode_result.bytes_decoded);
+        assert_eq!(3, first.decode_result.bytes_consumed);
+
+        let second = &inspection.segments[1];
+        assert_eq!(2, second.length);
+        assert_eq!(4, second.decode_result.bytes_decoded);
+        assert!((second.compression_ratio() - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn inspect_without_segment_length_sizes_from_runs() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[129, 5, 0]);
+
+        let inspection = inspect(&encoded, None).unwrap();
+        let segment = &inspection.segments[0];
+        assert_eq!(128, segment.decode_result.bytes_decoded);
+        assert_eq!(2, segment.decode_result.bytes_consumed);
+        assert!(segment.decode_result.literal_run_underflow);
+    }
+
+    #[test]
+    fn segment_result_matches_decode_segment() {
+        let segments: [&[u8]; 5] = [
+            &[1, 7, 8, 253, 9],
+            &[1, 7, 8, 253, 9, 128],
+            &[0, 7, 2, 1],
+            &[0, 7, 129],
+            &[],
+        ];
+        for segment in &segments {
+            for length in 0..8 {
+                let mut decoded = vec![0; length];
+                let expected = decode_segment(segment, &mut decoded, 1);
+                assert_eq!(expected, segment_result(segment, Some(length)));
+            }
+        }
+    }
+
+    #[test]
+    fn inspect_does_not_allocate_the_decoded_segment() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[129, 5]);
+
+        let inspection = inspect(&encoded, Some(usize::MAX)).unwrap();
+        assert_eq!(128, inspection.segments[0].decode_result.bytes_decoded);
+        assert!(!inspection.segments[0].decode_result.decoded_overflow);
+    }
+}
@@ -1,9 +1,35 @@
 //#![no_std]
 
+#[cfg(feature = "rayon")]
+pub mod batch;
+pub mod crop;
 pub mod decode;
-mod decode_segment;
+pub mod decode_segment;
+pub mod descriptor;
 pub mod diagnostics;
+pub mod encode_segment;
 pub mod error;
-mod get_segments;
-mod header;
+#[cfg(feature = "png")]
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod get_segments;
+pub mod header;
+pub mod inspect;
+pub mod interleave;
+pub mod limits;
+pub mod part10;
+pub mod pitched;
+pub mod preview;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod region;
+pub mod remap;
+mod spans;
+pub mod statistics;
+pub mod stored_bits;
 mod test;
+pub mod thumbnail;
+pub mod tokens;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,239 @@
+use crate::descriptor::ImageDescriptor;
+use crate::error::Error;
+use std::fmt;
+
+/// The resource limits that can be exceeded, see `DecodeLimits`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Limit {
+    /// the size of a decoded frame in bytes
+    DecodedBytes,
+
+    /// Rows (0028,0010)
+    Rows,
+
+    /// Columns (0028,0011)
+    Columns,
+
+    /// the number of frames
+    Frames,
+
+    /// the size of an encoded frame in bytes
+    EncodedBytes,
+
+    /// the size of all the decoded frames of an image together in bytes
+    VolumeBytes,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Limit::DecodedBytes => "decoded bytes",
+            Limit::Rows => "rows",
+            Limit::Columns => "columns",
+            Limit::Frames => "frames",
+            Limit::EncodedBytes => "encoded bytes",
+            Limit::VolumeBytes => "volume bytes",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Limits on the resources used to decode untrusted input.  The image
+/// attributes that size the decoded buffers come from the file being decoded,
+/// so the `_with_limits` functions check them against these limits before
+/// allocating anything and return `Error::LimitExceeded` if one is exceeded.
+///
+/// The defaults allow any image a DICOM modality is likely to produce.  Use
+/// `DecodeLimits::unlimited()` to disable the checks.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DecodeLimits {
+    /// the maximum size of a decoded frame in bytes
+    pub max_decoded_bytes: usize,
+
+    /// the maximum number of rows
+    pub max_rows: usize,
+
+    /// the maximum number of columns
+    pub max_columns: usize,
+
+    /// the maximum number of frames
+    pub max_frames: usize,
+
+    /// the maximum size of an encoded frame in bytes
+    pub max_encoded_bytes: usize,
+
+    /// the maximum size of all the decoded frames of an image together in
+    /// bytes, as decoded by `BatchDecoder::decode_volume()`
+    pub max_volume_bytes: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> DecodeLimits {
+        DecodeLimits {
+            max_decoded_bytes: 1024 * 1024 * 1024,
+            max_rows: 65535,
+            max_columns: 65535,
+            max_frames: 10000,
+            max_encoded_bytes: 1024 * 1024 * 1024,
+            // no more than can be allocated on 32 bit targets
+            max_volume_bytes: (4 * 1024 * 1024 * 1024u64).min(isize::MAX as u64) as usize,
+        }
+    }
+}
+
+// returns an error if value is greater than maximum
+fn check(limit: Limit, value: usize, maximum: usize) -> Result<(), Error> {
+    if value > maximum {
+        return Err(Error::LimitExceeded {
+            limit,
+            value,
+            maximum,
+        });
+    }
+    Ok(())
+}
+
+impl DecodeLimits {
+    /// Returns limits that allow anything
+    pub fn unlimited() -> DecodeLimits {
+        DecodeLimits {
+            max_decoded_bytes: usize::MAX,
+            max_rows: usize::MAX,
+            max_columns: usize::MAX,
+            max_frames: usize::MAX,
+            max_encoded_bytes: usize::MAX,
+            max_volume_bytes: usize::MAX,
+        }
+    }
+
+    /// Checks the dimensions and decoded size of a frame.  A decoded size too
+    /// large to represent is reported as `usize::MAX`.
+    pub fn check_descriptor(&self, descriptor: &ImageDescriptor) -> Result<(), Error> {
+        check(Limit::Rows, descriptor.rows, self.max_rows)?;
+        check(Limit::Columns, descriptor.columns, self.max_columns)?;
+        let decoded_length = descriptor.checked_decoded_length().unwrap_or(usize::MAX);
+        check(Limit::DecodedBytes, decoded_length, self.max_decoded_bytes)
+    }
+
+    /// Checks the number of frames
+    pub fn check_frames(&self, frames: usize) -> Result<(), Error> {
+        check(Limit::Frames, frames, self.max_frames)
+    }
+
+    /// Checks the size of an encoded frame
+    pub fn check_encoded(&self, encoded: &[u8]) -> Result<(), Error> {
+        check(Limit::EncodedBytes, encoded.len(), self.max_encoded_bytes)
+    }
+
+    /// Checks the decoded size of a number of frames together.  A size too
+    /// large to represent is reported as `usize::MAX`.
+    pub fn check_volume(&self, frames: usize, frame_length: usize) -> Result<(), Error> {
+        let volume_length = frames.saturating_mul(frame_length);
+        check(Limit::VolumeBytes, volume_length, self.max_volume_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeLimits, Limit};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+
+    fn exceeded(result: Result<(), Error>) -> (Limit, usize, usize) {
+        match result {
+            Err(Error::LimitExceeded {
+                limit,
+                value,
+                maximum,
+            }) => (limit, value, maximum),
+            other => panic!("expected a limit error but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn descriptor_within_limits() {
+        let limits = DecodeLimits::default();
+        limits
+            .check_descriptor(&ImageDescriptor::new(4096, 5120, 1, 16))
+            .unwrap();
+    }
+
+    #[test]
+    fn descriptor_limits() {
+        let limits = DecodeLimits {
+            max_decoded_bytes: 1000,
+            max_rows: 10,
+            max_columns: 20,
+            ..DecodeLimits::default()
+        };
+        assert_eq!(
+            (Limit::Rows, 11, 10),
+            exceeded(limits.check_descriptor(&ImageDescriptor::new(11, 20, 1, 8)))
+        );
+        assert_eq!(
+            (Limit::Columns, 21, 20),
+            exceeded(limits.check_descriptor(&ImageDescriptor::new(10, 21, 1, 8)))
+        );
+        assert_eq!(
+            (Limit::DecodedBytes, 1200, 1000),
+            exceeded(limits.check_descriptor(&ImageDescriptor::new(10, 20, 3, 16)))
+        );
+    }
+
+    #[test]
+    fn decoded_length_overflow_exceeds_limit() {
+        let limits = DecodeLimits {
+            max_rows: usize::MAX,
+            max_columns: usize::MAX,
+            ..DecodeLimits::default()
+        };
+        let descriptor = ImageDescriptor::new(usize::MAX, 2, 1, 8);
+        assert_eq!(
+            (Limit::DecodedBytes, usize::MAX, limits.max_decoded_bytes),
+            exceeded(limits.check_descriptor(&descriptor))
+        );
+    }
+
+    #[test]
+    fn frames_and_encoded_limits() {
+        let limits = DecodeLimits {
+            max_frames: 2,
+            max_encoded_bytes: 3,
+            ..DecodeLimits::default()
+        };
+        limits.check_frames(2).unwrap();
+        limits.check_encoded(&[0; 3]).unwrap();
+        assert_eq!((Limit::Frames, 3, 2), exceeded(limits.check_frames(3)));
+        assert_eq!(
+            (Limit::EncodedBytes, 4, 3),
+            exceeded(limits.check_encoded(&[0; 4]))
+        );
+    }
+
+    #[test]
+    fn volume_limit() {
+        let limits = DecodeLimits {
+            max_volume_bytes: 1000,
+            ..DecodeLimits::default()
+        };
+        limits.check_volume(10, 100).unwrap();
+        assert_eq!(
+            (Limit::VolumeBytes, 1010, 1000),
+            exceeded(limits.check_volume(10, 101))
+        );
+        assert_eq!(
+            (Limit::VolumeBytes, usize::MAX, 1000),
+            exceeded(limits.check_volume(usize::MAX, 2))
+        );
+    }
+
+    #[test]
+    fn unlimited_allows_anything() {
+        let limits = DecodeLimits::unlimited();
+        limits
+            .check_descriptor(&ImageDescriptor::new(usize::MAX, usize::MAX, 3, 16))
+            .unwrap();
+        limits.check_frames(usize::MAX).unwrap();
+        limits.check_volume(usize::MAX, usize::MAX).unwrap();
+    }
+}
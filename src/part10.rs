@@ -0,0 +1,614 @@
+use crate::descriptor::ImageDescriptor;
+use crate::error::Error;
+use crate::limits::DecodeLimits;
+use crate::stored_bits::StoredBits;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The Transfer Syntax UID for RLE Lossless
+pub const RLE_LOSSLESS: &str = "1.2.840.10008.1.2.5";
+
+const ITEM: (u16, u16) = (0xfffe, 0xe000);
+const ITEM_DELIMITATION: (u16, u16) = (0xfffe, 0xe00d);
+const SEQUENCE_DELIMITATION: (u16, u16) = (0xfffe, 0xe0dd);
+
+const MANUFACTURER: (u16, u16) = (0x0008, 0x0070);
+const SAMPLES_PER_PIXEL: (u16, u16) = (0x0028, 0x0002);
+const PHOTOMETRIC_INTERPRETATION: (u16, u16) = (0x0028, 0x0004);
+const NUMBER_OF_FRAMES: (u16, u16) = (0x0028, 0x0008);
+const ROWS: (u16, u16) = (0x0028, 0x0010);
+const COLUMNS: (u16, u16) = (0x0028, 0x0011);
+const BITS_ALLOCATED: (u16, u16) = (0x0028, 0x0100);
+const BITS_STORED: (u16, u16) = (0x0028, 0x0101);
+const HIGH_BIT: (u16, u16) = (0x0028, 0x0102);
+const PIXEL_REPRESENTATION: (u16, u16) = (0x0028, 0x0103);
+const WINDOW_CENTER: (u16, u16) = (0x0028, 0x1050);
+const WINDOW_WIDTH: (u16, u16) = (0x0028, 0x1051);
+const RESCALE_INTERCEPT: (u16, u16) = (0x0028, 0x1052);
+const RESCALE_SLOPE: (u16, u16) = (0x0028, 0x1053);
+const PIXEL_DATA: (u16, u16) = (0x7fe0, 0x0010);
+const TRANSFER_SYNTAX_UID: (u16, u16) = (0x0002, 0x0010);
+
+const UNDEFINED_LENGTH: u32 = 0xffff_ffff;
+
+/// The image related contents of a DICOM Part 10 file containing an RLE
+/// Lossless encoded image.  The frames borrow from the file bytes.
+#[derive(Debug)]
+pub struct Part10<'a> {
+    /// Transfer Syntax UID (0002,0010)
+    pub transfer_syntax_uid: String,
+
+    /// Manufacturer (0008,0070), if present
+    pub manufacturer: Option<String>,
+
+    /// the layout of each decoded frame
+    pub descriptor: ImageDescriptor,
+
+    /// Number of Frames (0028,0008), or 1 if not present
+    pub number_of_frames: usize,
+
+    /// Photometric Interpretation (0028,0004), if present
+    pub photometric_interpretation: Option<String>,
+
+    /// Bits Stored (0028,0101), or Bits Allocated if not present
+    pub bits_stored: usize,
+
+    /// High Bit (0028,0102), or one less than Bits Stored if not present
+    pub high_bit: usize,
+
+    /// Pixel Representation (0028,0103), or 0 (unsigned) if not present
+    pub pixel_representation: usize,
+
+    /// the first value of Window Center (0028,1050), if present
+    pub window_center: Option<f64>,
+
+    /// the first value of Window Width (0028,1051), if present
+    pub window_width: Option<f64>,
+
+    /// Rescale Intercept (0028,1052), if present
+    pub rescale_intercept: Option<f64>,
+
+    /// Rescale Slope (0028,1053), if present
+    pub rescale_slope: Option<f64>,
+
+    /// the RLE encoded frames
+    pub frames: Vec<&'a [u8]>,
+}
+
+impl<'a> Part10<'a> {
+    /// Returns the bits of each sample that hold the stored value, for
+    /// normalizing 16 bit samples with the `stored_bits` module
+    pub fn stored_bits(&self) -> StoredBits {
+        StoredBits::new(self.bits_stored, self.high_bit, self.pixel_representation)
+    }
+}
+
+struct Element {
+    tag: (u16, u16),
+    vr: [u8; 2],
+    length: u32,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if length > self.bytes.len() - self.position {
+            return Err(Error::Part10("unexpected eof".to_owned()));
+        }
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(LittleEndian::read_u16(self.read_bytes(2)?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(LittleEndian::read_u32(self.read_bytes(4)?))
+    }
+
+    fn read_tag(&mut self) -> Result<(u16, u16), Error> {
+        Ok((self.read_u16()?, self.read_u16()?))
+    }
+
+    fn peek_tag(&mut self) -> Result<(u16, u16), Error> {
+        let position = self.position;
+        let tag = self.read_tag();
+        self.position = position;
+        tag
+    }
+
+    // reads the tag and length of an item or delimiter, which never have a VR
+    fn read_item_header(&mut self) -> Result<((u16, u16), u32), Error> {
+        Ok((self.read_tag()?, self.read_u32()?))
+    }
+
+    // reads the header of an explicit VR little endian data element
+    fn read_element_header(&mut self) -> Result<Element, Error> {
+        let tag = self.read_tag()?;
+        let vr_bytes = self.read_bytes(2)?;
+        let vr = [vr_bytes[0], vr_bytes[1]];
+        let length = if has_long_length(&vr) {
+            self.read_bytes(2)?;
+            self.read_u32()?
+        } else {
+            self.read_u16()? as u32
+        };
+        Ok(Element { tag, vr, length })
+    }
+
+    fn read_value(&mut self, element: &Element) -> Result<&'a [u8], Error> {
+        if element.length == UNDEFINED_LENGTH {
+            return Err(Error::Part10(format!(
+                "unexpected undefined length for ({:04x},{:04x})",
+                element.tag.0, element.tag.1
+            )));
+        }
+        self.read_bytes(element.length as usize)
+    }
+
+    fn skip_value(&mut self, element: &Element) -> Result<(), Error> {
+        if element.length == UNDEFINED_LENGTH {
+            self.skip_sequence()
+        } else {
+            self.read_bytes(element.length as usize).map(|_| ())
+        }
+    }
+
+    // skips the items of an undefined length sequence up to and including the
+    // sequence delimitation item
+    fn skip_sequence(&mut self) -> Result<(), Error> {
+        loop {
+            let (tag, length) = self.read_item_header()?;
+            if tag == SEQUENCE_DELIMITATION {
+                return Ok(());
+            }
+            if tag != ITEM {
+                return Err(Error::Part10("invalid item in sequence".to_owned()));
+            }
+            if length == UNDEFINED_LENGTH {
+                self.skip_item()?;
+            } else {
+                self.read_bytes(length as usize)?;
+            }
+        }
+    }
+
+    // skips the elements of an undefined length item up to and including the
+    // item delimitation item
+    fn skip_item(&mut self) -> Result<(), Error> {
+        loop {
+            if self.peek_tag()? == ITEM_DELIMITATION {
+                self.read_item_header()?;
+                return Ok(());
+            }
+            let element = self.read_element_header()?;
+            self.skip_value(&element)?;
+        }
+    }
+}
+
+// VRs with a 2 byte reserved field followed by a 4 byte length
+fn has_long_length(vr: &[u8; 2]) -> bool {
+    matches!(
+        vr,
+        b"OB"
+            | b"OD"
+            | b"OF"
+            | b"OL"
+            | b"OV"
+            | b"OW"
+            | b"SQ"
+            | b"SV"
+            | b"UC"
+            | b"UN"
+            | b"UR"
+            | b"UT"
+            | b"UV"
+    )
+}
+
+fn to_string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .trim()
+        .to_owned()
+}
+
+fn to_us(element: &Element, value: &[u8]) -> Result<usize, Error> {
+    if value.len() != 2 || &element.vr != b"US" {
+        return Err(Error::Part10(format!(
+            "invalid value for ({:04x},{:04x})",
+            element.tag.0, element.tag.1
+        )));
+    }
+    Ok(LittleEndian::read_u16(value) as usize)
+}
+
+// returns the first value of a decimal string
+fn to_ds(element: &Element, value: &[u8]) -> Result<f64, Error> {
+    let value = to_string(value);
+    let first = value.split('\\').next().unwrap_or("").trim();
+    first.parse().map_err(|_| {
+        Error::Part10(format!(
+            "invalid value {} for ({:04x},{:04x})",
+            value, element.tag.0, element.tag.1
+        ))
+    })
+}
+
+fn required(value: Option<usize>, name: &str) -> Result<usize, Error> {
+    value.ok_or_else(|| Error::Part10(format!("missing {}", name)))
+}
+
+// reads the fragments of encapsulated pixel data, skipping the basic offset table
+fn read_fragments<'a>(reader: &mut Reader<'a>) -> Result<Vec<&'a [u8]>, Error> {
+    let mut fragments = Vec::new();
+
+    let (tag, length) = reader.read_item_header()?;
+    if tag != ITEM || length == UNDEFINED_LENGTH {
+        return Err(Error::Part10("missing basic offset table".to_owned()));
+    }
+    reader.read_bytes(length as usize)?;
+
+    loop {
+        let (tag, length) = reader.read_item_header()?;
+        if tag == SEQUENCE_DELIMITATION {
+            return Ok(fragments);
+        }
+        if tag != ITEM || length == UNDEFINED_LENGTH {
+            return Err(Error::Part10("invalid pixel data fragment".to_owned()));
+        }
+        fragments.push(reader.read_bytes(length as usize)?);
+    }
+}
+
+/// Parses a DICOM Part 10 file containing an RLE Lossless encoded image and
+/// returns the image attributes along with the encoded frames.  Returns errors
+/// in the following cases
+///     1) The file does not have the DICM prefix
+///     2) The transfer syntax is not RLE Lossless
+///     3) The image pixel attributes or pixel data are missing or invalid
+///     4) The number of fragments does not match the number of frames
+///
+/// # Arguments
+///
+/// * `bytes` - The contents of the DICOM Part 10 file
+///
+pub fn read_part10(bytes: &[u8]) -> Result<Part10<'_>, Error> {
+    read_part10_with_limits(bytes, &DecodeLimits::unlimited())
+}
+
+/// Same as `read_part10()` but also checks the image attributes, the number
+/// of frames, the decoded size of all frames together and the size of each
+/// encoded frame against the limits so the frames can be decoded without
+/// allocating more than the limits allow.
+/// Returns `Error::LimitExceeded` if a limit is exceeded.
+///
+/// # Arguments
+///
+/// * `bytes`  - The contents of the DICOM Part 10 file
+///
+/// * `limits` - The limits to enforce
+///
+pub fn read_part10_with_limits<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<Part10<'a>, Error> {
+    if bytes.len() < 132 || &bytes[128..132] != b"DICM" {
+        return Err(Error::Part10("missing DICM prefix".to_owned()));
+    }
+
+    let mut reader = Reader {
+        bytes,
+        position: 132,
+    };
+
+    // read the file meta information group
+    let mut transfer_syntax_uid = None;
+    while !reader.is_empty() && reader.peek_tag()?.0 == 0x0002 {
+        let element = reader.read_element_header()?;
+        if element.tag == TRANSFER_SYNTAX_UID {
+            transfer_syntax_uid = Some(to_string(reader.read_value(&element)?));
+        } else {
+            reader.skip_value(&element)?;
+        }
+    }
+    let transfer_syntax_uid =
+        transfer_syntax_uid.ok_or_else(|| Error::Part10("missing transfer syntax".to_owned()))?;
+    if transfer_syntax_uid != RLE_LOSSLESS {
+        return Err(Error::Part10(format!(
+            "transfer syntax {} is not RLE Lossless",
+            transfer_syntax_uid
+        )));
+    }
+
+    // read the data set, which is explicit VR little endian for RLE Lossless
+    let mut manufacturer = None;
+    let mut samples_per_pixel = None;
+    let mut number_of_frames = None;
+    let mut rows = None;
+    let mut columns = None;
+    let mut bits_allocated = None;
+    let mut bits_stored = None;
+    let mut high_bit = None;
+    let mut photometric_interpretation = None;
+    let mut pixel_representation = None;
+    let mut window_center = None;
+    let mut window_width = None;
+    let mut rescale_intercept = None;
+    let mut rescale_slope = None;
+    let mut fragments = None;
+    while !reader.is_empty() {
+        let element = reader.read_element_header()?;
+        match element.tag {
+            MANUFACTURER => manufacturer = Some(to_string(reader.read_value(&element)?)),
+            SAMPLES_PER_PIXEL => {
+                samples_per_pixel = Some(to_us(&element, reader.read_value(&element)?)?)
+            }
+            NUMBER_OF_FRAMES => {
+                let value = to_string(reader.read_value(&element)?);
+                number_of_frames =
+                    Some(value.parse::<usize>().map_err(|_| {
+                        Error::Part10(format!("invalid number of frames {}", value))
+                    })?);
+            }
+            ROWS => rows = Some(to_us(&element, reader.read_value(&element)?)?),
+            COLUMNS => columns = Some(to_us(&element, reader.read_value(&element)?)?),
+            BITS_ALLOCATED => bits_allocated = Some(to_us(&element, reader.read_value(&element)?)?),
+            BITS_STORED => bits_stored = Some(to_us(&element, reader.read_value(&element)?)?),
+            HIGH_BIT => high_bit = Some(to_us(&element, reader.read_value(&element)?)?),
+            PHOTOMETRIC_INTERPRETATION => {
+                photometric_interpretation = Some(to_string(reader.read_value(&element)?))
+            }
+            PIXEL_REPRESENTATION => {
+                pixel_representation = Some(to_us(&element, reader.read_value(&element)?)?)
+            }
+            WINDOW_CENTER => window_center = Some(to_ds(&element, reader.read_value(&element)?)?),
+            WINDOW_WIDTH => window_width = Some(to_ds(&element, reader.read_value(&element)?)?),
+            RESCALE_INTERCEPT => {
+                rescale_intercept = Some(to_ds(&element, reader.read_value(&element)?)?)
+            }
+            RESCALE_SLOPE => rescale_slope = Some(to_ds(&element, reader.read_value(&element)?)?),
+            PIXEL_DATA => {
+                if element.length != UNDEFINED_LENGTH {
+                    return Err(Error::Part10("pixel data is not encapsulated".to_owned()));
+                }
+                fragments = Some(read_fragments(&mut reader)?);
+                break;
+            }
+            _ => reader.skip_value(&element)?,
+        }
+    }
+
+    let descriptor = ImageDescriptor::new(
+        required(rows, "rows")?,
+        required(columns, "columns")?,
+        required(samples_per_pixel, "samples per pixel")?,
+        required(bits_allocated, "bits allocated")?,
+    );
+    let number_of_frames = number_of_frames.unwrap_or(1);
+    let bits_stored = bits_stored.unwrap_or(descriptor.bits_allocated);
+    let high_bit = high_bit.unwrap_or_else(|| bits_stored.saturating_sub(1));
+    limits.check_descriptor(&descriptor)?;
+    limits.check_frames(number_of_frames)?;
+    let frame_length = descriptor.checked_decoded_length().unwrap_or(usize::MAX);
+    limits.check_volume(number_of_frames, frame_length)?;
+    let frames = fragments.ok_or_else(|| Error::Part10("missing pixel data".to_owned()))?;
+    for frame in &frames {
+        limits.check_encoded(frame)?;
+    }
+
+    // each RLE frame is encoded in exactly one fragment
+    if frames.len() != number_of_frames {
+        return Err(Error::Part10(format!(
+            "expected {} fragments but found {}",
+            number_of_frames,
+            frames.len()
+        )));
+    }
+
+    Ok(Part10 {
+        transfer_syntax_uid,
+        manufacturer,
+        descriptor,
+        number_of_frames,
+        photometric_interpretation,
+        bits_stored,
+        high_bit,
+        pixel_representation: pixel_representation.unwrap_or(0),
+        window_center,
+        window_width,
+        rescale_intercept,
+        rescale_slope,
+        frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RLE_LOSSLESS;
+    use super::{read_part10, read_part10_with_limits};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::limits::{DecodeLimits, Limit};
+    use crate::stored_bits::StoredBits;
+    use crate::test::tests::{element, image_pixel, item, make_part10, pad};
+
+    #[test]
+    fn single_frame() {
+        let descriptor = ImageDescriptor::new(2, 3, 1, 16);
+        let mut data_set = vec![element(0x0008, 0x0070, b"LO", &pad("ACME"))];
+        data_set.extend(image_pixel(&descriptor));
+        let bytes = make_part10(&data_set, &[&[1, 2, 3, 4]]);
+
+        let part10 = read_part10(&bytes).unwrap();
+        assert_eq!(RLE_LOSSLESS, part10.transfer_syntax_uid);
+        assert_eq!(Some("ACME".to_owned()), part10.manufacturer);
+        assert_eq!(descriptor, part10.descriptor);
+        assert_eq!(1, part10.number_of_frames);
+        assert_eq!(vec![&[1u8, 2, 3, 4][..]], part10.frames);
+        assert_eq!(None, part10.photometric_interpretation);
+        assert_eq!(16, part10.bits_stored);
+        assert_eq!(15, part10.high_bit);
+        assert_eq!(0, part10.pixel_representation);
+        assert_eq!(None, part10.rescale_slope);
+    }
+
+    #[test]
+    fn display_attributes() {
+        let descriptor = ImageDescriptor::new(2, 2, 1, 16);
+        let mut data_set = image_pixel(&descriptor);
+        data_set.insert(1, element(0x0028, 0x0004, b"CS", &pad("MONOCHROME1")));
+        data_set.extend(vec![
+            element(0x0028, 0x0101, b"US", &12u16.to_le_bytes()),
+            element(0x0028, 0x0102, b"US", &11u16.to_le_bytes()),
+            element(0x0028, 0x0103, b"US", &1u16.to_le_bytes()),
+            element(0x0028, 0x1050, b"DS", &pad("40\\400")),
+            element(0x0028, 0x1051, b"DS", &pad("350.5\\2000")),
+            element(0x0028, 0x1052, b"DS", &pad("-1024 ")),
+            element(0x0028, 0x1053, b"DS", &pad("1")),
+        ]);
+        let bytes = make_part10(&data_set, &[&[1, 2]]);
+
+        let part10 = read_part10(&bytes).unwrap();
+        assert_eq!(
+            Some("MONOCHROME1".to_owned()),
+            part10.photometric_interpretation
+        );
+        assert_eq!(StoredBits::new(12, 11, 1), part10.stored_bits());
+        assert_eq!(Some(40.0), part10.window_center);
+        assert_eq!(Some(350.5), part10.window_width);
+        assert_eq!(Some(-1024.0), part10.rescale_intercept);
+        assert_eq!(Some(1.0), part10.rescale_slope);
+    }
+
+    #[test]
+    fn multi_frame_after_undefined_length_sequence() {
+        let descriptor = ImageDescriptor::new(2, 2, 3, 8);
+        let mut sequence = Vec::new();
+        sequence.extend_from_slice(&[0x08, 0x00, 0x15, 0x11]);
+        sequence.extend_from_slice(b"SQ\0\0");
+        sequence.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+        sequence.extend(item(0xfffe, 0xe000, 0xffff_ffff));
+        sequence.extend(element(0x0008, 0x1150, b"UI", &pad("1.2.3")));
+        sequence.extend(item(0xfffe, 0xe00d, 0));
+        sequence.extend(item(0xfffe, 0xe000, 2));
+        sequence.extend_from_slice(&[0, 0]);
+        sequence.extend(item(0xfffe, 0xe0dd, 0));
+
+        let mut data_set = vec![sequence, element(0x0028, 0x0008, b"IS", b"2 ")];
+        data_set.extend(image_pixel(&descriptor));
+        let bytes = make_part10(&data_set, &[&[1, 2], &[3, 4, 5, 6]]);
+
+        let part10 = read_part10(&bytes).unwrap();
+        assert_eq!(None, part10.manufacturer);
+        assert_eq!(2, part10.number_of_frames);
+        assert_eq!(vec![&[1u8, 2][..], &[3u8, 4, 5, 6][..]], part10.frames);
+    }
+
+    #[test]
+    fn fragment_count_mismatch_is_an_error() {
+        let descriptor = ImageDescriptor::new(2, 2, 1, 8);
+        let mut data_set = vec![element(0x0028, 0x0008, b"IS", b"3 ")];
+        data_set.extend(image_pixel(&descriptor));
+        let bytes = make_part10(&data_set, &[&[1, 2], &[3, 4]]);
+
+        assert!(matches!(read_part10(&bytes), Err(Error::Part10(_))));
+    }
+
+    #[test]
+    fn missing_prefix_is_an_error() {
+        assert!(matches!(read_part10(&[0; 132]), Err(Error::Part10(_))));
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        let descriptor = ImageDescriptor::new(2, 2, 1, 8);
+        let mut bytes = make_part10(&image_pixel(&descriptor), &[&[1, 2]]);
+        bytes.truncate(bytes.len() - 10);
+
+        assert!(matches!(read_part10(&bytes), Err(Error::Part10(_))));
+    }
+
+    // returns the limit exceeded when reading a two frame 2x3 image
+    fn exceeded_limit(limits: &DecodeLimits) -> Option<Limit> {
+        let descriptor = ImageDescriptor::new(2, 3, 1, 8);
+        let mut data_set = vec![element(0x0028, 0x0008, b"IS", b"2 ")];
+        data_set.extend(image_pixel(&descriptor));
+        let bytes = make_part10(&data_set, &[&[1, 2], &[3, 4, 5, 6]]);
+
+        match read_part10_with_limits(&bytes, limits) {
+            Ok(_) => None,
+            Err(Error::LimitExceeded { limit, .. }) => Some(limit),
+            Err(error) => panic!("unexpected error {}", error),
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let limits = DecodeLimits {
+            max_decoded_bytes: 6,
+            max_rows: 2,
+            max_columns: 3,
+            max_frames: 2,
+            max_encoded_bytes: 4,
+            max_volume_bytes: 12,
+        };
+        assert_eq!(None, exceeded_limit(&limits));
+        for (limit, limits) in [
+            (
+                Limit::Rows,
+                DecodeLimits {
+                    max_rows: 1,
+                    ..limits
+                },
+            ),
+            (
+                Limit::Columns,
+                DecodeLimits {
+                    max_columns: 2,
+                    ..limits
+                },
+            ),
+            (
+                Limit::DecodedBytes,
+                DecodeLimits {
+                    max_decoded_bytes: 5,
+                    ..limits
+                },
+            ),
+            (
+                Limit::Frames,
+                DecodeLimits {
+                    max_frames: 1,
+                    ..limits
+                },
+            ),
+            (
+                Limit::EncodedBytes,
+                DecodeLimits {
+                    max_encoded_bytes: 3,
+                    ..limits
+                },
+            ),
+            (
+                Limit::VolumeBytes,
+                DecodeLimits {
+                    max_volume_bytes: 11,
+                    ..limits
+                },
+            ),
+        ] {
+            assert_eq!(Some(limit), exceeded_limit(&limits));
+        }
+    }
+}
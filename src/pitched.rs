@@ -0,0 +1,211 @@
+//! Decodes an image into a destination whose rows start a fixed number of
+//! bytes apart, such as a texture staging buffer with aligned rows, without
+//! decoding into a temporary buffer first.  Each segment writes its runs
+//! straight into the rows they belong to.
+
+use crate::decode::{calculate_start_index, check_segment_count, checked_decoded_length};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::spans::for_each_span;
+
+/// Returns the smallest destination in bytes that can hold the image with the
+/// given row pitch and pixel stride, or an error if a row does not fit in the
+/// row pitch, the pixel stride is smaller than a pixel or the size overflows.
+///
+/// # Arguments
+///
+/// * `descriptor`   - The layout of the image
+///
+/// * `row_pitch`    - The number of bytes from the start of one row to the
+///   start of the next
+///
+/// * `pixel_stride` - The number of bytes from the start of one pixel to the
+///   start of the next, or None for pixels packed like decode()
+///
+pub fn pitched_length(
+    descriptor: &ImageDescriptor,
+    row_pitch: usize,
+    pixel_stride: Option<usize>,
+) -> Result<usize, Error> {
+    checked_decoded_length(descriptor)?;
+    let pixel_length = descriptor.segment_count();
+    let pixel_stride = pixel_stride.unwrap_or(pixel_length);
+    if pixel_stride < pixel_length {
+        return Err(Error::Format(format!(
+            "pixel stride {} is smaller than a pixel of {} bytes",
+            pixel_stride, pixel_length
+        )));
+    }
+    if descriptor.rows == 0 || descriptor.columns == 0 {
+        return Ok(0);
+    }
+
+    let overflow = || Error::Format("pitched image size overflows".to_string());
+    let row_length = (descriptor.columns - 1)
+        .checked_mul(pixel_stride)
+        .and_then(|length| length.checked_add(pixel_length))
+        .ok_or_else(overflow)?;
+    if row_length > row_pitch {
+        return Err(Error::Format(format!(
+            "row pitch {} is smaller than a row of {} bytes",
+            row_pitch, row_length
+        )));
+    }
+    (descriptor.rows - 1)
+        .checked_mul(row_pitch)
+        .and_then(|length| length.checked_add(row_length))
+        .ok_or_else(overflow)
+}
+
+/// Decodes a DICOM RLE Image into a destination with the given row pitch and
+/// pixel stride.  The bytes of each pixel are laid out like the output of
+/// decode() and the bytes between pixels and after the end of each row are
+/// left untouched.  Returns an error if the decoded buffer is smaller than
+/// `pitched_length()`, the pitch or stride is invalid or the number of
+/// segments in the header does not match the descriptor.
+///
+/// # Arguments
+///
+/// * `encoded`      - The encoded RLE image
+///
+/// * `descriptor`   - The layout of the image
+///
+/// * `decoded`      - The decoded buffer, at least `pitched_length()` bytes
+///
+/// * `row_pitch`    - The number of bytes from the start of one row to the
+///   start of the next
+///
+/// * `pixel_stride` - The number of bytes from the start of one pixel to the
+///   start of the next, or None for pixels packed like decode()
+///
+pub fn decode_pitched(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    decoded: &mut [u8],
+    row_pitch: usize,
+    pixel_stride: Option<usize>,
+) -> Result<DecodeDiagnostics, Error> {
+    let expected_length = pitched_length(descriptor, row_pitch, pixel_stride)?;
+    check_segment_count(encoded, descriptor)?;
+
+    if decoded.len() < expected_length {
+        return Err(Error::Format(format!(
+            "expected a decoded buffer of at least {} bytes but found {}",
+            expected_length,
+            decoded.len()
+        )));
+    }
+
+    let mut diagnostics = DecodeDiagnostics::new();
+    if expected_length == 0 {
+        return Ok(diagnostics);
+    }
+
+    let segments = get_segments(encoded)?;
+    let segment_count = segments.len();
+    let pixel_stride = pixel_stride.unwrap_or(segment_count);
+    let segment_length = descriptor.segment_length();
+
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let start_index = calculate_start_index(segment_count, segment_index);
+        let result = for_each_span(
+            segment,
+            descriptor.columns,
+            segment_length,
+            0..segment_length,
+            |row, column, span| {
+                let start = row * row_pitch + column * pixel_stride + start_index;
+                span.write(&mut decoded[start..], pixel_stride);
+            },
+        );
+
+        if result.bytes_decoded != segment_length {
+            diagnostics.incomplete_decode = true;
+        }
+        diagnostics.decode_segment_results[segment_index] = Some(result);
+    }
+
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_pitched, pitched_length};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_image;
+
+    // 2 rows of 3 columns of 16 bit values, the high byte is 1 then 2 across
+    // the row boundary and the low byte the index of the pixel
+    const SEGMENTS: [&[u8]; 2] = [&[254, 1, 254, 2], &[5, 0, 1, 2, 3, 4, 5]];
+
+    #[test]
+    fn row_pitch() {
+        let descriptor = ImageDescriptor::new(2, 3, 1, 16);
+        assert_eq!(14, pitched_length(&descriptor, 8, None).unwrap());
+        let mut decoded = vec![0xff; 16];
+        let diagnostics =
+            decode_pitched(&make_image(&SEGMENTS), &descriptor, &mut decoded, 8, None).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(
+            vec![0, 1, 1, 1, 2, 1, 0xff, 0xff, 3, 2, 4, 2, 5, 2, 0xff, 0xff],
+            decoded
+        );
+    }
+
+    #[test]
+    fn pixel_stride() {
+        // 2 rows of 2 columns of 8 bit values written as RGBA red
+        let encoded = make_image(&[&[3, 1, 2, 3, 4]]);
+        let descriptor = ImageDescriptor::new(2, 2, 1, 8);
+        assert_eq!(13, pitched_length(&descriptor, 8, Some(4)).unwrap());
+        let mut decoded = vec![0; 16];
+        decode_pitched(&encoded, &descriptor, &mut decoded, 8, Some(4)).unwrap();
+        assert_eq!(
+            vec![1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0],
+            decoded
+        );
+    }
+
+    #[test]
+    fn truncated_image_is_incomplete() {
+        let mut encoded = make_image(&SEGMENTS);
+        encoded.truncate(70);
+        let descriptor = ImageDescriptor::new(2, 3, 1, 16);
+        let mut decoded = vec![0; 12];
+        let diagnostics = decode_pitched(&encoded, &descriptor, &mut decoded, 6, None).unwrap();
+        assert!(diagnostics.incomplete_decode);
+    }
+
+    #[test]
+    fn row_pitch_smaller_than_row_is_an_error() {
+        let descriptor = ImageDescriptor::new(2, 3, 1, 16);
+        let mut decoded = vec![0; 12];
+        let result = decode_pitched(&make_image(&SEGMENTS), &descriptor, &mut decoded, 5, None);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn pixel_stride_smaller_than_pixel_is_an_error() {
+        let descriptor = ImageDescriptor::new(2, 3, 1, 16);
+        let mut decoded = vec![0; 12];
+        let result = decode_pitched(
+            &make_image(&SEGMENTS),
+            &descriptor,
+            &mut decoded,
+            6,
+            Some(1),
+        );
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn small_buffer_is_an_error() {
+        let descriptor = ImageDescriptor::new(2, 3, 1, 16);
+        let mut decoded = vec![0; 13];
+        let result = decode_pitched(&make_image(&SEGMENTS), &descriptor, &mut decoded, 8, None);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
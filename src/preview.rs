@@ -0,0 +1,142 @@
+//! Decodes a coarse preview of a 16 bit image from the most significant byte
+//! of each sample.  Those bytes are stored in their own segment ahead of the
+//! least significant bytes, so a viewer can show the preview as soon as the
+//! first segment has arrived and refine it once the whole image has.
+
+use crate::decode::{check_segment_count, checked_decoded_length};
+use crate::decode_segment::decode_segment;
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+
+/// Decodes the most significant byte of each sample of a DICOM RLE Image with
+/// 16 or more bits allocated into a new 8 bit buffer with the samples of each
+/// pixel interleaved.  Only the header and the segments holding the most
+/// significant bytes are read, so `encoded` may end part way through the
+/// other segments.  For signed data the preview values are i8.  Returns an
+/// error if the image has fewer than 16 bits allocated or the number of
+/// segments in the header does not match the descriptor.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image, possibly truncated after the most
+///   significant segments
+///
+/// * `descriptor` - The layout of the image
+///
+pub fn decode_preview(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(Vec<u8>, DecodeDiagnostics), Error> {
+    let bytes_per_sample = descriptor.bytes_per_sample();
+    if bytes_per_sample < 2 {
+        return Err(Error::Format(format!(
+            "expected at least 16 bits allocated but found {}",
+            descriptor.bits_allocated
+        )));
+    }
+    checked_decoded_length(descriptor)?;
+    check_segment_count(encoded, descriptor)?;
+
+    let samples_per_pixel = descriptor.samples_per_pixel;
+    let mut decoded = vec![0; descriptor.segment_length() * samples_per_pixel];
+    let segments = get_segments(encoded)?;
+
+    let mut diagnostics = DecodeDiagnostics::new();
+    for sample in 0..samples_per_pixel {
+        let segment_index = sample * bytes_per_sample;
+        let sample_decoded = decoded.get_mut(sample..).unwrap_or(&mut []);
+        let result = decode_segment(segments[segment_index], sample_decoded, samples_per_pixel);
+        if result.bytes_decoded != descriptor.segment_length() {
+            diagnostics.incomplete_decode = true;
+        }
+        diagnostics.decode_segment_results[segment_index] = Some(result);
+    }
+
+    Ok((decoded, diagnostics))
+}
+
+/// Decodes the most significant byte of each sample of a 16 bit DICOM RLE
+/// Image into a new buffer of 16 bit samples with the least significant byte
+/// zeroed, see `decode_preview()`.  The samples can be reinterpreted as i16
+/// for signed data.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image, possibly truncated after the most
+///   significant segments
+///
+/// * `descriptor` - The layout of the image
+///
+pub fn decode_preview_u16(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(Vec<u16>, DecodeDiagnostics), Error> {
+    if descriptor.bits_allocated != 16 {
+        return Err(Error::Format(format!(
+            "expected 16 bits allocated but found {}",
+            descriptor.bits_allocated
+        )));
+    }
+    let (preview, diagnostics) = decode_preview(encoded, descriptor)?;
+    let decoded = preview.iter().map(|value| (*value as u16) << 8).collect();
+    Ok((decoded, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_preview, decode_preview_u16};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_image;
+
+    // 1 row of 3 columns with the values 0x1234, 0x1256 and 0xff78, truncated
+    // after the first byte of the least significant segment
+    const SEGMENTS: [&[u8]; 2] = [&[255, 0x12, 0, 0xff], &[2]];
+
+    #[test]
+    fn preview() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let (decoded, diagnostics) = decode_preview(&make_image(&SEGMENTS), &descriptor).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![0x12, 0x12, 0xff], decoded);
+        assert!(diagnostics.decode_segment_results[1].is_none());
+    }
+
+    #[test]
+    fn preview_u16() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let (decoded, _) = decode_preview_u16(&make_image(&SEGMENTS), &descriptor).unwrap();
+        assert_eq!(vec![0x1200, 0x1200, 0xff00], decoded);
+        assert_eq!(-256, decoded[2] as i16);
+    }
+
+    #[test]
+    fn preview_color() {
+        // 1 pixel with the samples 0x0102, 0x0304 and 0x0506
+        let encoded = make_image(&[&[0, 1], &[0, 2], &[0, 3], &[0, 4], &[0, 5], &[0, 6]]);
+        let descriptor = ImageDescriptor::new(1, 1, 3, 16);
+        let (decoded, diagnostics) = decode_preview(&encoded, &descriptor).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![1, 3, 5], decoded);
+    }
+
+    #[test]
+    fn truncated_preview_is_incomplete() {
+        let mut encoded = make_image(&SEGMENTS);
+        encoded.truncate(66);
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let (decoded, diagnostics) = decode_preview(&encoded, &descriptor).unwrap();
+        assert!(diagnostics.incomplete_decode);
+        assert_eq!(vec![0x12, 0x12, 0], decoded);
+    }
+
+    #[test]
+    fn eight_bit_image_is_an_error() {
+        let encoded = make_image(&[&[0, 1]]);
+        let descriptor = ImageDescriptor::new(1, 1, 1, 8);
+        let result = decode_preview(&encoded, &descriptor);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::decode::{decode_frame, decode_frame_i16, decode_frame_u16};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::stored_bits::{
+    decode_frame_i16_stored, decode_frame_u16_stored, optional_stored_bits, StoredBits,
+};
+use numpy::{Element, PyArray1, PyArrayMethods};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+create_exception!(
+    dicomrle,
+    DicomRleError,
+    PyException,
+    "Base class for errors raised by dicomrle"
+);
+create_exception!(
+    dicomrle,
+    FormatError,
+    DicomRleError,
+    "The image is not formatted properly"
+);
+create_exception!(
+    dicomrle,
+    Part10Error,
+    DicomRleError,
+    "The DICOM Part 10 file could not be read"
+);
+create_exception!(
+    dicomrle,
+    ExportError,
+    DicomRleError,
+    "The image could not be exported"
+);
+create_exception!(
+    dicomrle,
+    LimitError,
+    DicomRleError,
+    "A value read from the image exceeded a decode limit"
+);
+
+fn to_py_err(error: Error) -> PyErr {
+    let message = error.to_string();
+    match error {
+        Error::Format(_) => FormatError::new_err(message),
+        Error::Part10(_) => Part10Error::new_err(message),
+        Error::Export(_) => ExportError::new_err(message),
+        Error::LimitExceeded { .. } => LimitError::new_err(message),
+    }
+}
+
+// converts diagnostics to a dict:
+// { "incomplete_decode", "segments": [{ "bytes_decoded", "bytes_consumed",
+//   "literal_run_underflow", "replicated_run_underflow", "decoded_overflow",
+//   "invalid_prefix" }] }
+fn diagnostics_to_dict<'py>(
+    py: Python<'py>,
+    diagnostics: &DecodeDiagnostics,
+) -> PyResult<Bound<'py, PyDict>> {
+    let segments = PyList::empty(py);
+    for result in diagnostics.decode_segment_results.iter().flatten() {
+        let segment = PyDict::new(py);
+        segment.set_item("bytes_decoded", result.bytes_decoded)?;
+        segment.set_item("bytes_consumed", result.bytes_consumed)?;
+        segment.set_item("literal_run_underflow", result.literal_run_underflow)?;
+        segment.set_item("replicated_run_underflow", result.replicated_run_underflow)?;
+        segment.set_item("decoded_overflow", result.decoded_overflow)?;
+        segment.set_item("invalid_prefix", result.invalid_prefix)?;
+        segments.append(segment)?;
+    }
+
+    let dict = PyDict::new(py);
+    dict.set_item("incomplete_decode", diagnostics.incomplete_decode)?;
+    dict.set_item("segments", segments)?;
+    Ok(dict)
+}
+
+// returns the number of bits allocated for a NumPy dtype name
+fn bits_allocated(dtype: &str) -> PyResult<usize> {
+    match dtype {
+        "uint8" => Ok(8),
+        "int16" | "uint16" => Ok(16),
+        _ => Err(PyValueError::new_err(format!(
+            "unsupported dtype {}, expected uint8, int16 or uint16",
+            dtype
+        ))),
+    }
+}
+
+// returns the shape of the NumPy array, color images have a trailing samples
+// dimension
+fn shape(descriptor: &ImageDescriptor) -> Vec<usize> {
+    if descriptor.samples_per_pixel == 1 {
+        vec![descriptor.rows, descriptor.columns]
+    } else {
+        vec![
+            descriptor.rows,
+            descriptor.columns,
+            descriptor.samples_per_pixel,
+        ]
+    }
+}
+
+fn to_python<'py, T: Element>(
+    py: Python<'py>,
+    result: Result<(Vec<T>, DecodeDiagnostics), Error>,
+    descriptor: &ImageDescriptor,
+) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyDict>)> {
+    let (pixels, diagnostics) = result.map_err(to_py_err)?;
+    let pixels = PyArray1::from_vec(py, pixels).reshape(shape(descriptor))?;
+    Ok((pixels.into_any(), diagnostics_to_dict(py, &diagnostics)?))
+}
+
+// returns the stored bits for the bits_stored, high_bit and
+// pixel_representation arguments, which only apply to int16 and uint16.
+// pixel_representation defaults to signed for int16 and unsigned for uint16.
+fn stored_bits(
+    dtype: &str,
+    bits_stored: Option<usize>,
+    high_bit: Option<usize>,
+    pixel_representation: Option<usize>,
+) -> PyResult<Option<StoredBits>> {
+    let default_representation = match dtype {
+        "int16" => 1,
+        "uint16" => 0,
+        _ if bits_stored.is_none() && high_bit.is_none() && pixel_representation.is_none() => {
+            return Ok(None)
+        }
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "bits_stored, high_bit and pixel_representation are not supported for dtype {}",
+                dtype
+            )))
+        }
+    };
+    Ok(optional_stored_bits(
+        bits_stored,
+        high_bit,
+        pixel_representation.unwrap_or(default_representation),
+    ))
+}
+
+/// Decodes a DICOM RLE Image and returns a tuple of the pixels as a NumPy
+/// array with shape (rows, columns) or (rows, columns, samples_per_pixel) and
+/// the diagnostics as a dict.  For int16 and uint16, giving bits_stored or
+/// high_bit masks and sign extends the samples.  The GIL is released while
+/// decoding.
+#[pyfunction]
+#[pyo3(signature = (
+    encoded,
+    rows,
+    columns,
+    samples_per_pixel = 1,
+    dtype = "uint8",
+    bits_stored = None,
+    high_bit = None,
+    pixel_representation = None
+))]
+#[allow(clippy::too_many_arguments)]
+fn decode<'py>(
+    py: Python<'py>,
+    encoded: &[u8],
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    dtype: &str,
+    bits_stored: Option<usize>,
+    high_bit: Option<usize>,
+    pixel_representation: Option<usize>,
+) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyDict>)> {
+    let descriptor = ImageDescriptor::new(rows, columns, samples_per_pixel, bits_allocated(dtype)?);
+    let stored = stored_bits(dtype, bits_stored, high_bit, pixel_representation)?;
+
+    match (dtype, stored) {
+        ("int16", Some(stored)) => {
+            let result = py.detach(|| decode_frame_i16_stored(encoded, &descriptor, &stored));
+            to_python(py, result, &descriptor)
+        }
+        ("int16", None) => {
+            let result = py.detach(|| decode_frame_i16(encoded, &descriptor));
+            to_python(py, result, &descriptor)
+        }
+        ("uint16", Some(stored)) => {
+            let result = py.detach(|| decode_frame_u16_stored(encoded, &descriptor, &stored));
+            to_python(py, result, &descriptor)
+        }
+        ("uint16", None) => {
+            let result = py.detach(|| decode_frame_u16(encoded, &descriptor));
+            to_python(py, result, &descriptor)
+        }
+        _ => {
+            let result = py.detach(|| decode_frame(encoded, &descriptor));
+            to_python(py, result, &descriptor)
+        }
+    }
+}
+
+/// Python extension module, built with maturin
+#[pymodule]
+fn dicomrle(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_function(wrap_pyfunction!(decode, module)?)?;
+    module.add("DicomRleError", py.get_type::<DicomRleError>())?;
+    module.add("FormatError", py.get_type::<FormatError>())?;
+    module.add("Part10Error", py.get_type::<Part10Error>())?;
+    module.add("ExportError", py.get_type::<ExportError>())?;
+    module.add("LimitError", py.get_type::<LimitError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        bits_allocated, diagnostics_to_dict, shape, stored_bits, to_py_err, DicomRleError,
+        FormatError,
+    };
+    use crate::decode::decode;
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::stored_bits::StoredBits;
+    use crate::test::tests::make_header;
+    use pyo3::prelude::*;
+    use pyo3::types::PyList;
+
+    #[test]
+    fn errors_map_to_exceptions() {
+        Python::initialize();
+        Python::attach(|py| {
+            let error = to_py_err(Error::Format("bad".to_owned()));
+            assert!(error.is_instance_of::<FormatError>(py));
+            assert!(error.is_instance_of::<DicomRleError>(py));
+            assert_eq!("format error: bad", error.value(py).to_string());
+        });
+    }
+
+    #[test]
+    fn diagnostics_dict() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[1, 7]);
+        let mut decoded = [0; 2];
+        let diagnostics = decode(&encoded, &mut decoded).unwrap();
+
+        Python::initialize();
+        Python::attach(|py| {
+            let dict = diagnostics_to_dict(py, &diagnostics).unwrap();
+            let incomplete: bool = dict
+                .get_item("incomplete_decode")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(incomplete);
+            let segments = dict.get_item("segments").unwrap().unwrap();
+            let segments = segments.cast::<PyList>().unwrap();
+            assert_eq!(1, segments.len());
+            let underflow: bool = segments
+                .get_item(0)
+                .unwrap()
+                .get_item("literal_run_underflow")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(underflow);
+        });
+    }
+
+    #[test]
+    fn shape_and_dtype() {
+        assert_eq!(vec![2, 3], shape(&ImageDescriptor::new(2, 3, 1, 16)));
+        assert_eq!(vec![2, 3, 3], shape(&ImageDescriptor::new(2, 3, 3, 8)));
+        assert_eq!(16, bits_allocated("int16").unwrap());
+        assert!(bits_allocated("float32").is_err());
+    }
+
+    #[test]
+    fn stored_bits_arguments() {
+        assert_eq!(None, stored_bits("int16", None, None, None).unwrap());
+        assert_eq!(None, stored_bits("uint8", None, None, None).unwrap());
+        assert_eq!(
+            Some(StoredBits::new(12, 11, 1)),
+            stored_bits("int16", Some(12), None, None).unwrap()
+        );
+        assert_eq!(
+            Some(StoredBits::new(12, 11, 0)),
+            stored_bits("uint16", None, Some(11), None).unwrap()
+        );
+        assert_eq!(
+            Some(StoredBits::new(12, 11, 0)),
+            stored_bits("int16", Some(12), Some(11), Some(0)).unwrap()
+        );
+        assert!(stored_bits("uint8", Some(8), None, None).is_err());
+    }
+}
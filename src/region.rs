@@ -0,0 +1,245 @@
+//! Decodes a window of rows and columns of an image into a compact buffer.
+//! Runs before the window are skipped without being written and each segment
+//! stops at the end of the last row of the window, so a band near the top of
+//! a large image decodes in a fraction of the time of the whole image.
+
+use crate::decode::{calculate_start_index, check_segment_count, checked_decoded_length};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::spans::for_each_span;
+use std::ops::Range;
+
+// returns an error unless range is a non empty range within 0..size
+fn check_range(name: &str, range: &Range<usize>, size: usize) -> Result<(), Error> {
+    if range.start >= range.end || range.end > size {
+        return Err(Error::Format(format!(
+            "invalid {} {}..{} for an image with {} {}",
+            name, range.start, range.end, size, name
+        )));
+    }
+    Ok(())
+}
+
+// returns an error if the region is empty or outside the image, otherwise the
+// size of the decoded region in bytes
+fn check_region(
+    descriptor: &ImageDescriptor,
+    rows: &Range<usize>,
+    columns: &Range<usize>,
+) -> Result<usize, Error> {
+    check_range("rows", rows, descriptor.rows)?;
+    check_range("columns", columns, descriptor.columns)?;
+    checked_decoded_length(descriptor)?;
+    Ok(region_length(descriptor, rows, Some(columns)))
+}
+
+/// Returns the size in bytes of the decoded region
+///
+/// # Arguments
+///
+/// * `descriptor` - The layout of the whole image
+///
+/// * `rows`       - The range of rows in the region
+///
+/// * `columns`    - The range of columns in the region, or None for all
+///   columns
+///
+pub fn region_length(
+    descriptor: &ImageDescriptor,
+    rows: &Range<usize>,
+    columns: Option<&Range<usize>>,
+) -> usize {
+    let width = columns.map_or(descriptor.columns, |columns| columns.len());
+    rows.len() * width * descriptor.segment_count()
+}
+
+/// Decodes a region of a DICOM RLE Image into a buffer laid out like the
+/// output of decode() for an image the size of the region.  Returns an error
+/// if the region is empty or outside the image, the decoded buffer is not
+/// `region_length()` bytes or the number of segments in the header does not
+/// match the descriptor.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the whole image
+///
+/// * `rows`       - The range of rows to decode
+///
+/// * `columns`    - The range of columns to decode, or None for all columns
+///
+/// * `decoded`    - The decoded buffer, presized to `region_length()`
+///
+pub fn decode_region(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    rows: Range<usize>,
+    columns: Option<Range<usize>>,
+    decoded: &mut [u8],
+) -> Result<DecodeDiagnostics, Error> {
+    let columns = columns.unwrap_or(0..descriptor.columns);
+    let expected_length = check_region(descriptor, &rows, &columns)?;
+    check_segment_count(encoded, descriptor)?;
+
+    if decoded.len() != expected_length {
+        return Err(Error::Format(format!(
+            "expected a decoded buffer of {} bytes but found {}",
+            expected_length,
+            decoded.len()
+        )));
+    }
+
+    let segments = get_segments(encoded)?;
+    let segment_count = segments.len();
+    let width = columns.len();
+    let first_pixel = rows.start * descriptor.columns + columns.start;
+    let last_pixel = (rows.end - 1) * descriptor.columns + columns.end;
+
+    let mut diagnostics = DecodeDiagnostics::new();
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let start_index = calculate_start_index(segment_count, segment_index);
+        let mut bytes_decoded = 0;
+
+        let mut result = for_each_span(
+            segment,
+            descriptor.columns,
+            descriptor.segment_length(),
+            first_pixel..last_pixel,
+            |row, column, span| {
+                let start = column.max(columns.start);
+                let end = (column + span.len()).min(columns.end);
+                if !rows.contains(&row) || start >= end {
+                    return;
+                }
+                let pixel = (row - rows.start) * width + start - columns.start;
+                let destination = &mut decoded[pixel * segment_count + start_index..];
+                span.part(start - column, end - start)
+                    .write(destination, segment_count);
+                bytes_decoded += end - start;
+            },
+        );
+
+        result.bytes_decoded = bytes_decoded;
+        if bytes_decoded != rows.len() * width {
+            diagnostics.incomplete_decode = true;
+        }
+        diagnostics.decode_segment_results[segment_index] = Some(result);
+    }
+
+    Ok(diagnostics)
+}
+
+/// Decodes a region of a DICOM RLE Image into a new buffer, see
+/// `decode_region()`.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the whole image
+///
+/// * `rows`       - The range of rows to decode
+///
+/// * `columns`    - The range of columns to decode, or None for all columns
+///
+pub fn decode_region_frame(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    rows: Range<usize>,
+    columns: Option<Range<usize>>,
+) -> Result<(Vec<u8>, DecodeDiagnostics), Error> {
+    let all_columns = 0..descriptor.columns;
+    let decoded_length = check_region(descriptor, &rows, columns.as_ref().unwrap_or(&all_columns))?;
+    let mut decoded = vec![0; decoded_length];
+    let diagnostics = decode_region(encoded, descriptor, rows, columns, &mut decoded)?;
+    Ok((decoded, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_region, decode_region_frame, region_length};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_image;
+
+    // 3 rows of 4 columns of 16 bit values.  The high byte is the index of the
+    // pixel and the low byte the index up to 6, both runs of the low segment
+    // cross a row.  The high bytes are a literal run then padding and the low
+    // bytes a literal run then 6 x 6.
+    const SEGMENTS: [&[u8]; 2] = [
+        &[11, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 0],
+        &[5, 0, 1, 2, 3, 4, 5, 251, 6],
+    ];
+
+    #[test]
+    fn rows() {
+        let descriptor = ImageDescriptor::new(3, 4, 1, 16);
+        let (decoded, diagnostics) =
+            decode_region_frame(&make_image(&SEGMENTS), &descriptor, 1..2, None).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![4, 4, 5, 5, 6, 6, 6, 7], decoded);
+    }
+
+    #[test]
+    fn rows_and_columns() {
+        let descriptor = ImageDescriptor::new(3, 4, 1, 16);
+        let mut decoded = vec![0; region_length(&descriptor, &(1..3), Some(&(1..3)))];
+        let diagnostics = decode_region(
+            &make_image(&SEGMENTS),
+            &descriptor,
+            1..3,
+            Some(1..3),
+            &mut decoded,
+        )
+        .unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![5, 5, 6, 6, 6, 9, 6, 10], decoded);
+    }
+
+    #[test]
+    fn stops_after_the_region() {
+        let descriptor = ImageDescriptor::new(3, 4, 1, 8);
+        let encoded = make_image(&[&[253, 1, 253, 2, 253, 3, 128]]);
+
+        let (decoded, diagnostics) =
+            decode_region_frame(&encoded, &descriptor, 0..2, Some(2..3)).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![1, 2], decoded);
+        let result = diagnostics.decode_segment_results[0].unwrap();
+        assert_eq!(4, result.bytes_consumed);
+        assert!(!result.invalid_prefix);
+    }
+
+    #[test]
+    fn truncated_image_is_incomplete() {
+        let descriptor = ImageDescriptor::new(3, 4, 1, 8);
+        let encoded = make_image(&[&[253, 1]]);
+
+        let (_, diagnostics) = decode_region_frame(&encoded, &descriptor, 1..3, None).unwrap();
+        assert!(diagnostics.incomplete_decode);
+    }
+
+    #[test]
+    fn rows_outside_the_image_is_an_error() {
+        let descriptor = ImageDescriptor::new(3, 4, 1, 16);
+        let result = decode_region_frame(&make_image(&SEGMENTS), &descriptor, 2..4, None);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn wrong_buffer_size_is_an_error() {
+        let descriptor = ImageDescriptor::new(3, 4, 1, 16);
+        let mut decoded = vec![0; 4];
+        let result = decode_region(
+            &make_image(&SEGMENTS),
+            &descriptor,
+            0..1,
+            None,
+            &mut decoded,
+        );
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
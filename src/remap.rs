@@ -0,0 +1,262 @@
+//! Applies a lookup table to the values of 8 bit images without decoding
+//! them, for example to relabel a segmentation mask.  The run values and
+//! literal bytes are mapped through the table and written to a new segment,
+//! merging runs that become equal.
+
+use crate::decode::{check_segment_count, checked_decoded_length};
+use crate::descriptor::ImageDescriptor;
+use crate::encode_segment::SegmentWriter;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::header::write_header;
+use crate::tokens::{tokens, Token};
+
+// writes runs to a segment, ending them at the end of each row
+struct RowWriter {
+    writer: SegmentWriter,
+    columns: usize,
+    column: usize,
+}
+
+impl RowWriter {
+    fn push_run(&mut self, value: u8, mut count: usize) {
+        while count > 0 {
+            let row_count = count.min(self.columns - self.column);
+            self.writer.push_run(value, row_count);
+            count -= row_count;
+            self.column += row_count;
+            if self.column == self.columns {
+                self.writer.end_row();
+                self.column = 0;
+            }
+        }
+    }
+}
+
+/// Maps the values of an RLE segment through a lookup table and returns the
+/// new segment along with the number of bytes it decodes to.  Runs that
+/// become equal are merged within a row, but each row is encoded separately
+/// as the DICOM standard requires, even if runs in the segment cross rows.
+/// Returns an error if there are no columns, or the segment contains an
+/// invalid control byte or a truncated run other than a single zero byte
+/// padding the end of the segment.
+///
+/// # Arguments
+///
+/// * `segment` - The encoded RLE segment
+///
+/// * `columns` - The number of columns in the image
+///
+/// * `lut`     - The lookup table, value n is replaced by `lut[n]`
+///
+pub fn remap_segment(
+    segment: &[u8],
+    columns: usize,
+    lut: &[u8; 256],
+) -> Result<(Vec<u8>, usize), Error> {
+    if columns == 0 {
+        return Err(Error::Format(
+            "cannot remap a segment with 0 columns".to_string(),
+        ));
+    }
+    let mut writer = RowWriter {
+        writer: SegmentWriter::new(),
+        columns,
+        column: 0,
+    };
+    let mut decoded_length: usize = 0;
+
+    for token in tokens(segment) {
+        match token {
+            Token::Literal { offset, len } => {
+                for value in &segment[offset..offset + len] {
+                    writer.push_run(lut[*value as usize], 1);
+                }
+            }
+            Token::Replicate { value, len } => writer.push_run(lut[value as usize], len),
+            Token::LiteralUnderflow { position }
+                if position == segment.len() - 1 && segment[position] == 0 =>
+            {
+                // zero padding on the last byte (valid as per DICOM)
+                break;
+            }
+            Token::Invalid { position } => {
+                return Err(Error::Format(format!(
+                    "invalid control byte 128 at offset {}",
+                    position
+                )))
+            }
+            Token::LiteralUnderflow { position } => {
+                return Err(Error::Format(format!(
+                    "truncated literal run at offset {}",
+                    position
+                )))
+            }
+            Token::ReplicateUnderflow { position } => {
+                return Err(Error::Format(format!(
+                    "truncated replicated run at offset {}",
+                    position
+                )))
+            }
+        }
+        decoded_length = decoded_length.saturating_add(token.decoded_len());
+    }
+
+    Ok((writer.writer.finish(), decoded_length))
+}
+
+/// Maps the values of an 8 bit DICOM RLE Image through a lookup table and
+/// returns the new RLE image.  Every segment is mapped, so the same table is
+/// applied to each sample of a color image.  Returns an error if the image is
+/// not 8 bit, the number of segments in the header does not match the
+/// descriptor or a segment does not decode to exactly rows * columns bytes.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `lut`        - The lookup table, value n is replaced by `lut[n]`
+///
+pub fn remap_frame(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    lut: &[u8; 256],
+) -> Result<Vec<u8>, Error> {
+    if descriptor.bits_allocated != 8 {
+        return Err(Error::Format(format!(
+            "expected 8 bits allocated but found {}",
+            descriptor.bits_allocated
+        )));
+    }
+    checked_decoded_length(descriptor)?;
+    check_segment_count(encoded, descriptor)?;
+
+    let mut segments = Vec::new();
+    for (segment_index, segment) in get_segments(encoded)?.iter().enumerate() {
+        let (remapped, decoded_length) = remap_segment(segment, descriptor.columns, lut)
+            .map_err(|error| Error::Format(format!("segment {}: {}", segment_index, error)))?;
+        if decoded_length != descriptor.segment_length() {
+            return Err(Error::Format(format!(
+                "segment {} decodes to {} bytes but expected {}",
+                segment_index,
+                decoded_length,
+                descriptor.segment_length()
+            )));
+        }
+        segments.push(remapped);
+    }
+
+    let segment_lengths: Vec<usize> = segments.iter().map(|segment| segment.len()).collect();
+    let mut remapped = write_header(&segment_lengths)?;
+    for segment in segments {
+        remapped.extend(segment);
+    }
+
+    Ok(remapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remap_frame, remap_segment};
+    use crate::crop::crop_rows;
+    use crate::decode::decode_frame;
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_header;
+
+    fn relabel(from: u8, to: u8) -> [u8; 256] {
+        let mut lut = [0; 256];
+        for (index, value) in lut.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+        lut[from as usize] = to;
+        lut
+    }
+
+    #[test]
+    fn merges_runs_that_become_equal() {
+        // 3 x 1, literal 2 1, 4 x 2
+        let segment = [254, 1, 1, 2, 1, 253, 2];
+        let (remapped, decoded_length) = remap_segment(&segment, 9, &relabel(2, 1)).unwrap();
+        assert_eq!(vec![248, 1], remapped);
+        assert_eq!(9, decoded_length);
+    }
+
+    #[test]
+    fn encodes_each_row_separately() {
+        // 3 x 1 then 3 x 2 in rows of 2 columns, the runs cross rows
+        let segment = [254, 1, 254, 2];
+        let (remapped, decoded_length) = remap_segment(&segment, 2, &relabel(2, 1)).unwrap();
+        assert_eq!(vec![255, 1, 255, 1, 255, 1], remapped);
+        assert_eq!(6, decoded_length);
+    }
+
+    #[test]
+    fn ignores_zero_padding() {
+        let (remapped, decoded_length) = remap_segment(&[255, 4, 0], 2, &relabel(4, 5)).unwrap();
+        assert_eq!(vec![255, 5], remapped);
+        assert_eq!(2, decoded_length);
+    }
+
+    #[test]
+    fn invalid_control_byte_is_an_error() {
+        let result = remap_segment(&[255, 4, 128, 0], 2, &relabel(4, 5));
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn zero_columns_is_an_error() {
+        let result = remap_segment(&[255, 4], 0, &relabel(4, 5));
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn frame() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[3, 1, 2, 3, 2, 253, 3]);
+        let descriptor = ImageDescriptor::new(2, 4, 1, 8);
+
+        let remapped = remap_frame(&encoded, &descriptor, &relabel(3, 9)).unwrap();
+        let (decoded, diagnostics) = decode_frame(&remapped, &descriptor).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![1, 2, 9, 2, 9, 9, 9, 9], decoded);
+    }
+
+    #[test]
+    fn remapped_frame_can_be_cropped() {
+        // rows of 1 2 3 3 and 3 3 3 3, relabelling 1 and 2 to 3 makes every
+        // value equal
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[1, 1, 2, 251, 3]);
+        let descriptor = ImageDescriptor::new(2, 4, 1, 8);
+        let mut lut = relabel(1, 3);
+        lut[2] = 3;
+
+        let remapped = remap_frame(&encoded, &descriptor, &lut).unwrap();
+        let cropped = crop_rows(&remapped, 4, 1..2).unwrap();
+        let (decoded, _) = decode_frame(&cropped, &ImageDescriptor::new(1, 4, 1, 8)).unwrap();
+        assert_eq!(vec![3, 3, 3, 3], decoded);
+    }
+
+    #[test]
+    fn frame_with_wrong_length_is_an_error() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[253, 3]);
+        let descriptor = ImageDescriptor::new(2, 4, 1, 8);
+
+        let result = remap_frame(&encoded, &descriptor, &relabel(3, 9));
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn sixteen_bit_frame_is_an_error() {
+        let mut encoded = make_header(&mut vec![2, 64, 66]);
+        encoded.extend_from_slice(&[255, 3, 255, 3]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 16);
+
+        let result = remap_frame(&encoded, &descriptor, &relabel(3, 9));
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
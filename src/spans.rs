@@ -0,0 +1,280 @@
+use crate::decode_segment::DecodeSegmentResult;
+use crate::tokens::{tokens, Token, Tokens};
+use std::ops::Range;
+
+// a piece of a run that lies within a single row
+pub(crate) enum Span<'a> {
+    Literal(&'a [u8]),
+    Replicate(u8, usize),
+}
+
+impl<'a> Span<'a> {
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Span::Literal(values) => values.len(),
+            Span::Replicate(_, count) => *count,
+        }
+    }
+
+    // returns the value at index
+    pub(crate) fn value(&self, index: usize) -> u8 {
+        match self {
+            Span::Literal(values) => values[index],
+            Span::Replicate(value, _) => *value,
+        }
+    }
+
+    // returns count values starting at start
+    pub(crate) fn part(&self, start: usize, count: usize) -> Span<'a> {
+        match self {
+            Span::Literal(values) => Span::Literal(&values[start..start + count]),
+            Span::Replicate(value, _) => Span::Replicate(*value, count),
+        }
+    }
+
+    // writes the values to every increment-th byte of destination
+    pub(crate) fn write(&self, destination: &mut [u8], increment: usize) {
+        match increment {
+            1 => self.write_fixed::<1>(destination),
+            2 => self.write_fixed::<2>(destination),
+            3 => self.write_fixed::<3>(destination),
+            4 => self.write_fixed::<4>(destination),
+            _ => self.write_generic(destination, increment),
+        }
+    }
+
+    // writes the values with an increment known at compile time, see the
+    // fixed paths of decode_segment().  Spans are never empty
+    fn write_fixed<const INCREMENT: usize>(&self, destination: &mut [u8]) {
+        let destination = &mut destination[..(self.len() - 1) * INCREMENT + 1];
+        match self {
+            Span::Literal(values) => {
+                if INCREMENT == 1 {
+                    destination.copy_from_slice(values);
+                } else {
+                    for (byte, value) in destination.iter_mut().step_by(INCREMENT).zip(*values) {
+                        *byte = *value;
+                    }
+                }
+            }
+            Span::Replicate(value, _) => {
+                if INCREMENT == 1 {
+                    destination.fill(*value);
+                } else {
+                    for byte in destination.iter_mut().step_by(INCREMENT) {
+                        *byte = *value;
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_generic(&self, destination: &mut [u8], increment: usize) {
+        match self {
+            Span::Literal(values) => {
+                for (byte, value) in destination.iter_mut().step_by(increment).zip(*values) {
+                    *byte = *value;
+                }
+            }
+            Span::Replicate(value, count) => {
+                for byte in destination.iter_mut().step_by(increment).take(*count) {
+                    *byte = *value;
+                }
+            }
+        }
+    }
+}
+
+// the runs of a segment as spans, stopping like decode_segment() at the first
+// token that cannot be decoded or run that does not fit in segment_length
+// values.  result() has bytes_decoded and bytes_consumed set for the runs
+// returned so far and records why the runs stopped, if they did.
+pub(crate) struct Runs<'a> {
+    segment: &'a [u8],
+    tokens: Tokens<'a>,
+    remaining: usize,
+    result: DecodeSegmentResult,
+}
+
+impl<'a> Runs<'a> {
+    pub(crate) fn new(segment: &'a [u8], segment_length: usize) -> Runs<'a> {
+        Runs {
+            segment,
+            tokens: tokens(segment),
+            remaining: segment_length,
+            result: DecodeSegmentResult::default(),
+        }
+    }
+
+    pub(crate) fn result(&self) -> DecodeSegmentResult {
+        self.result
+    }
+}
+
+impl<'a> Iterator for Runs<'a> {
+    type Item = Span<'a>;
+
+    fn next(&mut self) -> Option<Span<'a>> {
+        let token = self.tokens.next()?;
+        let span = match token {
+            Token::Literal { offset, len } => Span::Literal(&self.segment[offset..offset + len]),
+            Token::Replicate { value, len } => Span::Replicate(value, len),
+            _ => {
+                self.result.record_stop(token);
+                return None;
+            }
+        };
+        if span.len() > self.remaining {
+            // no later run is returned once one does not fit
+            self.result.decoded_overflow = true;
+            self.tokens = tokens(&[]);
+            return None;
+        }
+
+        self.remaining -= span.len();
+        self.result.bytes_decoded += span.len();
+        self.result.bytes_consumed = self.tokens.position();
+        Some(span)
+    }
+}
+
+// walks the runs of a segment with `columns` values per row, calling visit
+// with the row, the column of the first value and the values of each piece of
+// a run within a row.  Runs that end before pixels.start are skipped without
+// being visited and the walk stops once pixels.end values have been covered.
+// Stops like `Runs` at the first token that cannot be decoded or run that does
+// not fit in segment_length values.  The returned result has bytes_decoded set
+// to the number of values walked.  columns must not be zero.
+pub(crate) fn for_each_span<F: FnMut(usize, usize, Span)>(
+    segment: &[u8],
+    columns: usize,
+    segment_length: usize,
+    pixels: Range<usize>,
+    mut visit: F,
+) -> DecodeSegmentResult {
+    let mut pixel_index = 0;
+    let (mut row, mut column) = (0, 0);
+
+    let mut runs = Runs::new(segment, segment_length);
+    while pixel_index < pixels.end {
+        let run = match runs.next() {
+            Some(run) => run,
+            None => break,
+        };
+        let len = run.len();
+
+        if pixel_index + len > pixels.start {
+            let (mut span_row, mut span_column) = (row, column);
+            let mut done = 0;
+            while done < len {
+                let count = (columns - span_column).min(len - done);
+                visit(span_row, span_column, run.part(done, count));
+                done += count;
+                span_row += 1;
+                span_column = 0;
+            }
+        }
+
+        // track the row and column of the next value without dividing unless
+        // the run crosses a row
+        pixel_index += len;
+        column += len;
+        if column >= columns {
+            row += column / columns;
+            column %= columns;
+        }
+    }
+
+    runs.result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_each_span, Runs, Span};
+    use crate::decode_segment::decode_segment;
+
+    fn walk(segment: &[u8], pixels: std::ops::Range<usize>) -> Vec<(usize, usize, Vec<u8>)> {
+        let mut pieces = Vec::new();
+        for_each_span(segment, 4, 12, pixels, |row, column, span| {
+            let mut values = vec![0; span.len()];
+            span.write(&mut values, 1);
+            pieces.push((row, column, values));
+        });
+        pieces
+    }
+
+    #[test]
+    fn splits_runs_at_row_boundaries() {
+        // 6 x 1 then literal 2, 3, 4
+        let segment = [251, 1, 2, 2, 3, 4];
+        assert_eq!(
+            vec![
+                (0, 0, vec![1, 1, 1, 1]),
+                (1, 0, vec![1, 1]),
+                (1, 2, vec![2, 3]),
+                (2, 0, vec![4])
+            ],
+            walk(&segment, 0..12)
+        );
+    }
+
+    #[test]
+    fn skips_runs_before_and_stops_after_pixels() {
+        // 4 x 1, 4 x 2, 4 x 3
+        let segment = [253, 1, 253, 2, 253, 3];
+        assert_eq!(vec![(1, 0, vec![2, 2, 2, 2])], walk(&segment, 5..6));
+
+        let result = for_each_span(&segment, 4, 12, 5..6, |_, _, _| {});
+        assert_eq!(4, result.bytes_consumed);
+        assert_eq!(8, result.bytes_decoded);
+    }
+
+    #[test]
+    fn stops_at_run_that_does_not_fit() {
+        let result = for_each_span(&[253, 1, 129, 2], 4, 12, 0..12, |_, _, _| {});
+        assert!(result.decoded_overflow);
+        assert_eq!(4, result.bytes_decoded);
+
+        let result = for_each_span(&[253, 1, 128], 4, 12, 0..12, |_, _, _| {});
+        assert!(result.invalid_prefix);
+    }
+
+    #[test]
+    fn runs_stop_at_run_that_does_not_fit() {
+        let mut runs = Runs::new(&[1, 5, 6, 129, 2, 0, 3], 4);
+        assert!(matches!(runs.next(), Some(Span::Literal(&[5, 6]))));
+        assert!(runs.next().is_none());
+        assert!(runs.next().is_none());
+        let result = runs.result();
+        assert!(result.decoded_overflow);
+        assert_eq!(2, result.bytes_decoded);
+        assert_eq!(3, result.bytes_consumed);
+    }
+
+    #[test]
+    fn runs_match_decode_segment() {
+        let segments: [&[u8]; 5] = [
+            &[1, 7, 8, 253, 9],
+            &[1, 7, 8, 253, 9, 128],
+            &[0, 7, 2, 1],
+            &[0, 7, 129],
+            &[],
+        ];
+        for segment in &segments {
+            for length in 0..8 {
+                let mut decoded = vec![0; length];
+                let expected = decode_segment(segment, &mut decoded, 1);
+                let mut runs = Runs::new(segment, length);
+                runs.by_ref().for_each(drop);
+                assert_eq!(expected, runs.result());
+            }
+        }
+    }
+
+    #[test]
+    fn writes_every_increment_byte() {
+        let mut destination = [0; 6];
+        Span::Replicate(7, 2).write(&mut destination[1..], 3);
+        assert_eq!([0, 7, 0, 0, 7, 0], destination);
+    }
+}
@@ -0,0 +1,354 @@
+//! Histogram and range of the pixel values of 8 and 16 bit grayscale images
+//! computed from the runs of their segments, without decoding them.  A
+//! replicated run of n values adds n to a single histogram bin in one step, so
+//! images with long runs are much quicker to analyse than to decode.
+
+use crate::decode::{check_segment_count, checked_decoded_length};
+use crate::descriptor::ImageDescriptor;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::spans::{Runs, Span};
+
+/// Histogram and range of the stored pixel values of an image
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelStatistics {
+    /// histogram of the stored values.  Index n holds the number of pixels
+    /// with the value n.  Has 256 entries for 8 bit images and 65536 for 16
+    /// bit images.  Signed values are counted as their unsigned bit pattern,
+    /// so the negative values are in the upper half.
+    pub histogram: Vec<usize>,
+
+    /// the smallest value, or None if no pixels were counted.  Negative if the
+    /// image is signed and has negative values.
+    pub min: Option<i32>,
+
+    /// the largest value, or None if no pixels were counted
+    pub max: Option<i32>,
+
+    /// the number of pixels counted.  Less than the number of pixels in the
+    /// image if a segment is truncated, contains an invalid control byte or
+    /// decodes to more pixels than the image has, in which case only the
+    /// pixels before the problem are counted.
+    pub pixel_count: usize,
+}
+
+impl PixelStatistics {
+    fn from_histogram(
+        histogram: Vec<usize>,
+        pixel_count: usize,
+        pixel_representation: usize,
+    ) -> PixelStatistics {
+        // the bit patterns in the upper half are negative if signed
+        let length = histogram.len() as i32;
+        let values = || {
+            histogram
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count != 0)
+                .map(|(index, _)| match index as i32 {
+                    index if pixel_representation == 1 && index >= length / 2 => index - length,
+                    index => index,
+                })
+        };
+        let min = values().min();
+        let max = values().max();
+        PixelStatistics {
+            histogram,
+            min,
+            max,
+            pixel_count,
+        }
+    }
+}
+
+/// Computes the statistics of an 8 bit grayscale image from its single
+/// segment.
+///
+/// # Arguments
+///
+/// * `segment`              - The encoded RLE segment
+///
+/// * `pixel_count`          - The number of pixels in the image (rows *
+///   columns)
+///
+/// * `pixel_representation` - Pixel Representation (0028,0103), 0 for
+///   unsigned and 1 for signed
+///
+pub fn segment_statistics(
+    segment: &[u8],
+    pixel_count: usize,
+    pixel_representation: usize,
+) -> PixelStatistics {
+    let mut histogram = vec![0; 256];
+    let mut counted = 0;
+
+    for run in Runs::new(segment, pixel_count) {
+        counted += run.len();
+        match run {
+            Span::Literal(values) => {
+                for value in values {
+                    histogram[*value as usize] += 1;
+                }
+            }
+            Span::Replicate(value, count) => histogram[value as usize] += count,
+        }
+    }
+
+    PixelStatistics::from_histogram(histogram, counted, pixel_representation)
+}
+
+/// Computes the statistics of a 16 bit grayscale image from its two segments.
+/// The segments are walked in lockstep so a replicated run in each adds to a
+/// single histogram bin for as long as the runs overlap.
+///
+/// # Arguments
+///
+/// * `high`                 - The encoded segment holding the most
+///   significant bytes, which is the first segment in the image
+///
+/// * `low`                  - The encoded segment holding the least
+///   significant bytes
+///
+/// * `pixel_count`          - The number of pixels in the image (rows *
+///   columns)
+///
+/// * `pixel_representation` - Pixel Representation (0028,0103), 0 for
+///   unsigned and 1 for signed
+///
+pub fn segment_pair_statistics(
+    high: &[u8],
+    low: &[u8],
+    pixel_count: usize,
+    pixel_representation: usize,
+) -> PixelStatistics {
+    let mut histogram = vec![0; 65536];
+    let mut counted = 0;
+
+    let word = |high: u8, low: u8| (high as usize) << 8 | low as usize;
+
+    let mut high_runs = Runs::new(high, pixel_count);
+    let mut low_runs = Runs::new(low, pixel_count);
+    let mut high_run = high_runs.next();
+    let mut low_run = low_runs.next();
+
+    while let (Some(high), Some(low)) = (&high_run, &low_run) {
+        let count = high.len().min(low.len());
+        match (high, low) {
+            (Span::Replicate(high_value, _), Span::Replicate(low_value, _)) => {
+                histogram[word(*high_value, *low_value)] += count;
+            }
+            (Span::Replicate(high_value, _), Span::Literal(low_values)) => {
+                for low_value in &low_values[..count] {
+                    histogram[word(*high_value, *low_value)] += 1;
+                }
+            }
+            (Span::Literal(high_values), Span::Replicate(low_value, _)) => {
+                for high_value in &high_values[..count] {
+                    histogram[word(*high_value, *low_value)] += 1;
+                }
+            }
+            (Span::Literal(high_values), Span::Literal(low_values)) => {
+                for (high_value, low_value) in high_values[..count].iter().zip(*low_values) {
+                    histogram[word(*high_value, *low_value)] += 1;
+                }
+            }
+        }
+        counted += count;
+
+        // keep what is left of the longer run for the next step
+        high_run = match high.len() - count {
+            0 => high_runs.next(),
+            left => Some(high.part(count, left)),
+        };
+        low_run = match low.len() - count {
+            0 => low_runs.next(),
+            left => Some(low.part(count, left)),
+        };
+    }
+
+    PixelStatistics::from_histogram(histogram, counted, pixel_representation)
+}
+
+/// Computes the statistics of an 8 or 16 bit grayscale DICOM RLE Image from
+/// its runs.  Returns an error if the image is not 8 or 16 bit grayscale, the
+/// pixel representation is not 0 or 1 or the number of segments in the header
+/// does not match the descriptor.
+///
+/// # Arguments
+///
+/// * `encoded`              - The encoded RLE image
+///
+/// * `descriptor`           - The layout of the decoded image
+///
+/// * `pixel_representation` - Pixel Representation (0028,0103), 0 for
+///   unsigned and 1 for signed
+///
+pub fn statistics(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    pixel_representation: usize,
+) -> Result<PixelStatistics, Error> {
+    if pixel_representation > 1 {
+        return Err(Error::Format(format!(
+            "expected a pixel representation of 0 or 1 but found {}",
+            pixel_representation
+        )));
+    }
+    if descriptor.samples_per_pixel != 1 {
+        return Err(Error::Format(format!(
+            "expected 1 sample per pixel but found {}",
+            descriptor.samples_per_pixel
+        )));
+    }
+    checked_decoded_length(descriptor)?;
+    check_segment_count(encoded, descriptor)?;
+
+    let segments = get_segments(encoded)?;
+    let pixel_count = descriptor.segment_length();
+    match segments.as_slice() {
+        [segment] => Ok(segment_statistics(
+            segment,
+            pixel_count,
+            pixel_representation,
+        )),
+        [high, low] => Ok(segment_pair_statistics(
+            high,
+            low,
+            pixel_count,
+            pixel_representation,
+        )),
+        _ => Err(Error::Format(format!(
+            "expected 8 or 16 bits allocated but found {}",
+            descriptor.bits_allocated
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{segment_pair_statistics, segment_statistics, statistics};
+    use crate::decode_segment::decode_segment;
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_header;
+
+    #[test]
+    fn eight_bit() {
+        // literal 5, 9, 5 then 72 copies of 7
+        let statistics = segment_statistics(&[2, 5, 9, 5, 185, 7], 75, 0);
+        assert_eq!(2, statistics.histogram[5]);
+        assert_eq!(1, statistics.histogram[9]);
+        assert_eq!(72, statistics.histogram[7]);
+        assert_eq!(75, statistics.pixel_count);
+        assert_eq!(Some(5), statistics.min);
+        assert_eq!(Some(9), statistics.max);
+    }
+
+    #[test]
+    fn eight_bit_stops_at_run_that_does_not_fit() {
+        // the replicated run of 72 does not fit in 10 pixels so only the
+        // literal run is counted, as decode_segment() would
+        let statistics = segment_statistics(&[2, 5, 9, 5, 185, 7], 10, 0);
+        assert_eq!(3, statistics.pixel_count);
+        assert_eq!(0, statistics.histogram[7]);
+    }
+
+    #[test]
+    fn counts_the_pixels_decode_segment_decodes() {
+        // literal 5, 9 then 4 x 7 then an invalid control byte
+        let segment = [1, 5, 9, 253, 7, 128, 0, 1];
+        let mut decoded = vec![0; 10];
+        let expected = decode_segment(&segment, &mut decoded, 1).bytes_decoded;
+        assert_eq!(6, expected);
+        assert_eq!(expected, segment_statistics(&segment, 10, 0).pixel_count);
+        let statistics = segment_pair_statistics(&segment, &segment, 10, 0);
+        assert_eq!(expected, statistics.pixel_count);
+    }
+
+    #[test]
+    fn empty_segment() {
+        let statistics = segment_statistics(&[], 10, 0);
+        assert_eq!(0, statistics.pixel_count);
+        assert_eq!(None, statistics.min);
+        assert_eq!(None, statistics.max);
+    }
+
+    #[test]
+    fn sixteen_bit_lockstep() {
+        // high bytes: 4 x 0x01, then literal 0x02 0x03
+        // low bytes:  literal 0x10 0x11, then 4 x 0x20
+        let high = [253, 0x01, 1, 0x02, 0x03];
+        let low = [1, 0x10, 0x11, 253, 0x20];
+        let statistics = segment_pair_statistics(&high, &low, 6, 0);
+        assert_eq!(6, statistics.pixel_count);
+        assert_eq!(1, statistics.histogram[0x0110]);
+        assert_eq!(1, statistics.histogram[0x0111]);
+        assert_eq!(2, statistics.histogram[0x0120]);
+        assert_eq!(1, statistics.histogram[0x0220]);
+        assert_eq!(1, statistics.histogram[0x0320]);
+        assert_eq!(Some(0x0110), statistics.min);
+        assert_eq!(Some(0x0320), statistics.max);
+    }
+
+    #[test]
+    fn sixteen_bit_stops_at_shorter_segment() {
+        let high = [131, 0xff]; // 126 x 0xff
+        let low = [129, 0x00, 0]; // 128 x 0x00 then a truncated literal run
+        let statistics = segment_pair_statistics(&high, &low, 200, 0);
+        assert_eq!(126, statistics.pixel_count);
+        assert_eq!(126, statistics.histogram[0xff00]);
+    }
+
+    #[test]
+    fn image() {
+        let mut encoded = make_header(&mut vec![2, 64, 66]);
+        encoded.extend_from_slice(&[255, 0x12, 255, 0x34]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 16);
+        let statistics = statistics(&encoded, &descriptor, 0).unwrap();
+        assert_eq!(2, statistics.histogram[0x1234]);
+        assert_eq!(2, statistics.pixel_count);
+    }
+
+    #[test]
+    fn signed_eight_bit() {
+        // literal -1, 5, -128
+        let statistics = segment_statistics(&[2, 0xff, 5, 0x80], 3, 1);
+        assert_eq!(1, statistics.histogram[0xff]);
+        assert_eq!(Some(-128), statistics.min);
+        assert_eq!(Some(5), statistics.max);
+    }
+
+    #[test]
+    fn signed_sixteen_bit() {
+        // -2, 0x0120 and -32768
+        let high = [2, 0xff, 0x01, 0x80];
+        let low = [2, 0xfe, 0x20, 0x00];
+        let statistics = segment_pair_statistics(&high, &low, 3, 1);
+        assert_eq!(1, statistics.histogram[0xfffe]);
+        assert_eq!(Some(-32768), statistics.min);
+        assert_eq!(Some(0x0120), statistics.max);
+
+        // only negative values
+        let statistics = segment_pair_statistics(&[255, 0xff], &[0, 0xfe, 0], 2, 1);
+        assert_eq!(Some(-2), statistics.min);
+        assert_eq!(Some(-2), statistics.max);
+    }
+
+    #[test]
+    fn color_image_is_an_error() {
+        let mut encoded = make_header(&mut vec![3, 64, 66, 68]);
+        encoded.extend_from_slice(&[255, 0, 255, 0, 255, 0]);
+        let descriptor = ImageDescriptor::new(1, 2, 3, 8);
+        let result = statistics(&encoded, &descriptor, 0);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn invalid_pixel_representation_is_an_error() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[255, 0]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 8);
+        let result = statistics(&encoded, &descriptor, 2);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
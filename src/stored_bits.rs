@@ -0,0 +1,308 @@
+//! Normalizes decoded 16 bit samples using the Bits Stored, High Bit and Pixel
+//! Representation attributes.  Only the bits from High Bit down are part of
+//! the value, the bits above may hold overlays or garbage and signed values
+//! with fewer than 16 bits stored need their sign extended before they can
+//! be read as i16.
+
+use crate::decode::{decode_frame_i16, decode_frame_u16};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+
+/// Describes which bits of each 16 bit sample hold the stored value
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StoredBits {
+    /// Bits Stored (0028,0101)
+    pub bits_stored: usize,
+
+    /// High Bit (0028,0102)
+    pub high_bit: usize,
+
+    /// Pixel Representation (0028,0103), 0 for unsigned and 1 for signed
+    pub pixel_representation: usize,
+}
+
+impl StoredBits {
+    pub fn new(bits_stored: usize, high_bit: usize, pixel_representation: usize) -> StoredBits {
+        StoredBits {
+            bits_stored,
+            high_bit,
+            pixel_representation,
+        }
+    }
+
+    // returns an error unless the stored bits fit in a 16 bit sample
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.bits_stored == 0
+            || self.high_bit >= 16
+            || self.bits_stored > self.high_bit + 1
+            || self.pixel_representation > 1
+        {
+            return Err(Error::Format(format!(
+                "invalid bits stored {}, high bit {} and pixel representation {} for 16 bits allocated",
+                self.bits_stored, self.high_bit, self.pixel_representation
+            )));
+        }
+        Ok(())
+    }
+
+    // returns the stored value of a sample, sign extended if signed
+    fn normalize(&self, sample: u16) -> u16 {
+        let unused_high_bits = 15 - self.high_bit;
+        let shift = 16 - self.bits_stored;
+        let value = sample << unused_high_bits;
+        if self.pixel_representation == 1 {
+            ((value as i16) >> shift) as u16
+        } else {
+            value >> shift
+        }
+    }
+
+    // true if every sample is already normalized
+    fn is_identity(&self) -> bool {
+        self.bits_stored == 16
+    }
+}
+
+// returns the stored bits for the optional Bits Stored and High Bit arguments
+// of the language bindings, or None if neither is given and the samples are
+// returned as encoded.  A missing argument is derived from the other one
+// assuming the value starts at bit 0.
+#[cfg(any(feature = "wasm", feature = "python"))]
+pub(crate) fn optional_stored_bits(
+    bits_stored: Option<usize>,
+    high_bit: Option<usize>,
+    pixel_representation: usize,
+) -> Option<StoredBits> {
+    let (bits_stored, high_bit) = match (bits_stored, high_bit) {
+        (None, None) => return None,
+        (Some(bits_stored), None) => (bits_stored, bits_stored.saturating_sub(1)),
+        (None, Some(high_bit)) => (high_bit.saturating_add(1), high_bit),
+        (Some(bits_stored), Some(high_bit)) => (bits_stored, high_bit),
+    };
+    Some(StoredBits::new(bits_stored, high_bit, pixel_representation))
+}
+
+/// Replaces each sample with its stored value, masking off the bits above
+/// High Bit and shifting the value down if it does not start at bit 0.
+/// Signed values are sign extended so the samples can be reinterpreted as
+/// i16.  Returns an error if the stored bits do not fit in 16 bits.
+///
+/// # Arguments
+///
+/// * `decoded` - The decoded samples
+///
+/// * `stored`  - The bits of each sample that hold the value
+///
+pub fn normalize_u16(decoded: &mut [u16], stored: &StoredBits) -> Result<(), Error> {
+    stored.check()?;
+    if !stored.is_identity() {
+        for sample in decoded.iter_mut() {
+            *sample = stored.normalize(*sample);
+        }
+    }
+    Ok(())
+}
+
+/// Same as `normalize_u16()` for samples decoded as i16
+///
+/// # Arguments
+///
+/// * `decoded` - The decoded samples
+///
+/// * `stored`  - The bits of each sample that hold the value
+///
+pub fn normalize_i16(decoded: &mut [i16], stored: &StoredBits) -> Result<(), Error> {
+    stored.check()?;
+    if !stored.is_identity() {
+        for sample in decoded.iter_mut() {
+            *sample = stored.normalize(*sample as u16) as i16;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the overlay plane held in bit `bit_position` of each sample as a
+/// bitmap, for example an overlay stored in the unused high bits of a 12 bit
+/// image.  The bitmap has one bit per sample packed the same way as Overlay
+/// Data (60xx,3000), the first sample in the least significant bit of the
+/// first byte.  `bit_position` is the Overlay Bit Position (60xx,0102) and must
+/// be above High Bit.  Call this before normalizing the samples, which clears
+/// these bits.  Returns an error if the stored bits do not fit in 16 bits or
+/// the bit position is not above High Bit.
+///
+/// # Arguments
+///
+/// * `decoded`      - The decoded samples
+///
+/// * `stored`       - The bits of each sample that hold the value
+///
+/// * `bit_position` - The bit of each sample that holds the overlay
+///
+pub fn overlay_bitmap(
+    decoded: &[u16],
+    stored: &StoredBits,
+    bit_position: usize,
+) -> Result<Vec<u8>, Error> {
+    stored.check()?;
+    if bit_position <= stored.high_bit || bit_position >= 16 {
+        return Err(Error::Format(format!(
+            "overlay bit position {} is not between high bit {} and 15",
+            bit_position, stored.high_bit
+        )));
+    }
+    let mut bitmap = vec![0; decoded.len().div_ceil(8)];
+    for (index, sample) in decoded.iter().enumerate() {
+        let bit = ((sample >> bit_position) & 1) as u8;
+        bitmap[index / 8] |= bit << (index % 8);
+    }
+    Ok(bitmap)
+}
+
+/// Decodes a 16 bit unsigned DICOM RLE Image like `decode_frame_u16()` and
+/// normalizes the samples, see `normalize_u16()`.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `stored`     - The bits of each sample that hold the value
+///
+pub fn decode_frame_u16_stored(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    stored: &StoredBits,
+) -> Result<(Vec<u16>, DecodeDiagnostics), Error> {
+    let (mut decoded, diagnostics) = decode_frame_u16(encoded, descriptor)?;
+    normalize_u16(&mut decoded, stored)?;
+    Ok((decoded, diagnostics))
+}
+
+/// Decodes a 16 bit signed DICOM RLE Image like `decode_frame_i16()` and
+/// normalizes the samples, see `normalize_i16()`.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the decoded image
+///
+/// * `stored`     - The bits of each sample that hold the value
+///
+pub fn decode_frame_i16_stored(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    stored: &StoredBits,
+) -> Result<(Vec<i16>, DecodeDiagnostics), Error> {
+    let (mut decoded, diagnostics) = decode_frame_i16(encoded, descriptor)?;
+    normalize_i16(&mut decoded, stored)?;
+    Ok((decoded, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_frame_i16_stored, normalize_i16, normalize_u16, overlay_bitmap, StoredBits,
+    };
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_header;
+
+    #[test]
+    fn masks_unsigned() {
+        let mut decoded = [0xf123, 0x0fff, 0x1000];
+        normalize_u16(&mut decoded, &StoredBits::new(12, 11, 0)).unwrap();
+        assert_eq!([0x0123, 0x0fff, 0], decoded);
+    }
+
+    #[test]
+    fn sign_extends_signed() {
+        let mut decoded = [0xf800u16, 0x07ff, 0x1fff, 0x0801].map(|sample| sample as i16);
+        normalize_i16(&mut decoded, &StoredBits::new(12, 11, 1)).unwrap();
+        assert_eq!([-2048, 2047, -1, -2047], decoded);
+    }
+
+    #[test]
+    fn shifts_values_not_at_bit_zero() {
+        // 12 bits stored in bits 3 to 14
+        let mut decoded = [0x8008, 0x7ff8];
+        normalize_u16(&mut decoded, &StoredBits::new(12, 14, 0)).unwrap();
+        assert_eq!([1, 0x0fff], decoded);
+
+        let mut decoded = [0x7ff8];
+        normalize_u16(&mut decoded, &StoredBits::new(12, 14, 1)).unwrap();
+        assert_eq!(-1, decoded[0] as i16);
+    }
+
+    #[test]
+    fn sixteen_bits_stored_is_unchanged() {
+        let mut decoded = [0x8001, 0xffff];
+        normalize_u16(&mut decoded, &StoredBits::new(16, 15, 1)).unwrap();
+        assert_eq!([0x8001, 0xffff], decoded);
+    }
+
+    #[test]
+    fn extracts_overlay_bitmap() {
+        let decoded = [0xf123, 0x1fff, 0x0fff, 0x1000, 0, 0, 0, 0, 0xf000];
+        let stored = StoredBits::new(12, 11, 0);
+        assert_eq!(
+            vec![0x0b, 0x01],
+            overlay_bitmap(&decoded, &stored, 12).unwrap()
+        );
+        assert_eq!(
+            vec![0x01, 0x01],
+            overlay_bitmap(&decoded, &stored, 15).unwrap()
+        );
+    }
+
+    #[test]
+    fn overlay_bit_position_in_the_stored_value_is_an_error() {
+        let decoded = [0xffff];
+        let stored = StoredBits::new(12, 11, 0);
+        let result = overlay_bitmap(&decoded, &stored, 11);
+        assert!(matches!(result, Err(Error::Format(_))));
+        let result = overlay_bitmap(&decoded, &stored, 16);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[cfg(any(feature = "wasm", feature = "python"))]
+    #[test]
+    fn optional_stored_bits_fills_in_missing_arguments() {
+        use super::optional_stored_bits;
+
+        assert_eq!(None, optional_stored_bits(None, None, 1));
+        assert_eq!(
+            Some(StoredBits::new(12, 11, 1)),
+            optional_stored_bits(Some(12), None, 1)
+        );
+        assert_eq!(
+            Some(StoredBits::new(12, 11, 0)),
+            optional_stored_bits(None, Some(11), 0)
+        );
+        assert_eq!(
+            Some(StoredBits::new(12, 14, 0)),
+            optional_stored_bits(Some(12), Some(14), 0)
+        );
+    }
+
+    #[test]
+    fn decode_and_sign_extend() {
+        // 1 row of 2 columns with the values 0xf800 and 0x17ff
+        let mut encoded = make_header(&mut vec![2, 64, 68]);
+        encoded.extend_from_slice(&[1, 0xf8, 0x17, 0, 1, 0x00, 0xff]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 16);
+        let (decoded, diagnostics) =
+            decode_frame_i16_stored(&encoded, &descriptor, &StoredBits::new(12, 11, 1)).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![-2048, 2047], decoded);
+    }
+
+    #[test]
+    fn bits_stored_above_high_bit_is_an_error() {
+        let mut decoded = [0];
+        let result = normalize_u16(&mut decoded, &StoredBits::new(12, 10, 0));
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
@@ -1,5 +1,7 @@
 #[cfg(test)]
 pub mod tests {
+    use crate::descriptor::ImageDescriptor;
+    use crate::part10::RLE_LOSSLESS;
     use byteorder::{ByteOrder, LittleEndian};
 
     pub fn make_header(values: &mut Vec<u32>) -> Vec<u8> {
@@ -7,12 +9,111 @@ pub mod tests {
         values.resize(16, 0);
 
         // allocate size for the header bytes
-        let mut header = Vec::new();
-        header.resize(64, 0);
+        let mut header = vec![0; 64];
 
         // write the u32s to the header bytes
-        LittleEndian::write_u32_into(&values, &mut header);
+        LittleEndian::write_u32_into(values, &mut header);
 
         header
     }
+
+    /// Makes an RLE image from its segments with the segment offsets in the
+    /// header computed from their lengths.  The last segment may be cut short
+    /// to make a truncated image.
+    pub fn make_image(segments: &[&[u8]]) -> Vec<u8> {
+        let mut values = vec![segments.len() as u32];
+        let mut offset = 64;
+        for segment in segments {
+            values.push(offset as u32);
+            offset += segment.len();
+        }
+
+        let mut encoded = make_header(&mut values);
+        for segment in segments {
+            encoded.extend_from_slice(segment);
+        }
+        encoded
+    }
+
+    pub fn element(group: u16, element: u16, vr: &[u8; 2], value: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&group.to_le_bytes());
+        bytes.extend_from_slice(&element.to_le_bytes());
+        bytes.extend_from_slice(vr);
+        match vr {
+            b"OB" | b"SQ" | b"UN" => {
+                bytes.extend_from_slice(&[0, 0]);
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            }
+            _ => bytes.extend_from_slice(&(value.len() as u16).to_le_bytes()),
+        }
+        bytes.extend_from_slice(value);
+        bytes
+    }
+
+    pub fn item(group: u16, element: u16, length: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&group.to_le_bytes());
+        bytes.extend_from_slice(&element.to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes
+    }
+
+    pub fn pad(value: &str) -> Vec<u8> {
+        let mut bytes = value.as_bytes().to_vec();
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// Makes a Part 10 file with an RLE Lossless transfer syntax.  The data set
+    /// elements are inserted before the pixel data which contains the frames
+    pub fn make_part10(data_set: &[Vec<u8>], frames: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes.extend_from_slice(b"DICM");
+        bytes.extend(element(0x0002, 0x0010, b"UI", &pad(RLE_LOSSLESS)));
+        for data_element in data_set {
+            bytes.extend(data_element);
+        }
+        bytes.extend_from_slice(&[0xe0, 0x7f, 0x10, 0x00]);
+        bytes.extend_from_slice(b"OB\0\0");
+        bytes.extend_from_slice(&0xffff_ffffu32.to_le_bytes());
+        bytes.extend(item(0xfffe, 0xe000, 0));
+        for frame in frames {
+            bytes.extend(item(0xfffe, 0xe000, frame.len() as u32));
+            bytes.extend_from_slice(frame);
+        }
+        bytes.extend(item(0xfffe, 0xe0dd, 0));
+        bytes
+    }
+
+    pub fn image_pixel(descriptor: &ImageDescriptor) -> Vec<Vec<u8>> {
+        vec![
+            element(
+                0x0028,
+                0x0002,
+                b"US",
+                &(descriptor.samples_per_pixel as u16).to_le_bytes(),
+            ),
+            element(
+                0x0028,
+                0x0010,
+                b"US",
+                &(descriptor.rows as u16).to_le_bytes(),
+            ),
+            element(
+                0x0028,
+                0x0011,
+                b"US",
+                &(descriptor.columns as u16).to_le_bytes(),
+            ),
+            element(
+                0x0028,
+                0x0100,
+                b"US",
+                &(descriptor.bits_allocated as u16).to_le_bytes(),
+            ),
+        ]
+    }
 }
@@ -0,0 +1,395 @@
+//! Decodes a downsampled copy of an image, for example to show thumbnails in a
+//! study browser.  The runs of the rows that are not needed are walked
+//! without being written.
+
+use crate::decode::{calculate_start_index, check_segment_count, checked_decoded_length};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::get_segments::get_segments;
+use crate::spans::{for_each_span, Span};
+
+/// How the pixels of each block of the image are reduced to one pixel of the
+/// thumbnail
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Downsample {
+    /// Keeps the top left pixel of each block.  Only the rows that are kept
+    /// are written and the decode stops after the last of them.
+    Nearest,
+
+    /// Averages the pixels of each block, treating samples as unsigned
+    /// integers (Pixel Representation 0)
+    Average,
+
+    /// Averages the pixels of each block, treating samples as two's complement
+    /// integers of Bits Allocated bits (Pixel Representation 1)
+    AverageSigned,
+}
+
+/// Returns the descriptor of a thumbnail made with the given factor.  Each
+/// pixel of the thumbnail comes from a block of `factor` x `factor` pixels,
+/// smaller at the right and bottom edges if the image is not a multiple of
+/// `factor` in size.
+///
+/// # Arguments
+///
+/// * `descriptor` - The layout of the image
+///
+/// * `factor`     - The downsampling factor, at least 1
+///
+pub fn thumbnail_descriptor(descriptor: &ImageDescriptor, factor: usize) -> ImageDescriptor {
+    let factor = factor.max(1);
+    ImageDescriptor {
+        rows: descriptor.rows.div_ceil(factor),
+        columns: descriptor.columns.div_ceil(factor),
+        ..*descriptor
+    }
+}
+
+/// Returns the smallest factor that makes a thumbnail no larger than
+/// `max_size` pixels in either direction
+///
+/// # Arguments
+///
+/// * `descriptor` - The layout of the image
+///
+/// * `max_size`   - The largest number of rows or columns in the thumbnail
+///
+pub fn thumbnail_factor(descriptor: &ImageDescriptor, max_size: usize) -> usize {
+    descriptor
+        .rows
+        .max(descriptor.columns)
+        .div_ceil(max_size.max(1))
+        .max(1)
+}
+
+// keeps the top left pixel of each block
+fn decode_nearest(
+    segments: &[&[u8]],
+    descriptor: &ImageDescriptor,
+    thumbnail: &ImageDescriptor,
+    factor: usize,
+    decoded: &mut [u8],
+) -> DecodeDiagnostics {
+    let segment_count = segments.len();
+    let columns = descriptor.columns;
+    let last_row = (thumbnail.rows - 1) * factor;
+    let last_pixel = last_row * columns + (thumbnail.columns - 1) * factor + 1;
+
+    let mut diagnostics = DecodeDiagnostics::new();
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let start_index = calculate_start_index(segment_count, segment_index);
+        let mut bytes_decoded = 0;
+
+        let mut result = for_each_span(
+            segment,
+            columns,
+            descriptor.segment_length(),
+            0..last_pixel,
+            |row, column, span| {
+                if row % factor != 0 {
+                    return;
+                }
+                let mut kept = column.div_ceil(factor) * factor;
+                while kept < column + span.len() {
+                    let pixel = row / factor * thumbnail.columns + kept / factor;
+                    decoded[pixel * segment_count + start_index] = span.value(kept - column);
+                    bytes_decoded += 1;
+                    kept += factor;
+                }
+            },
+        );
+
+        result.bytes_decoded = bytes_decoded;
+        if bytes_decoded != thumbnail.segment_length() {
+            diagnostics.incomplete_decode = true;
+        }
+        diagnostics.decode_segment_results[segment_index] = Some(result);
+    }
+    diagnostics
+}
+
+// averages the pixels of each block.  Each segment adds its bytes, shifted to
+// their place in the sample, to the sum for the sample so multi byte samples
+// are averaged as a whole.  Signed samples have their sign bit flipped before
+// they are added and after they are averaged, which maps them onto unsigned
+// values in the same order.
+fn decode_average(
+    segments: &[&[u8]],
+    descriptor: &ImageDescriptor,
+    thumbnail: &ImageDescriptor,
+    factor: usize,
+    signed: bool,
+    decoded: &mut [u8],
+) -> DecodeDiagnostics {
+    let segment_count = segments.len();
+    let columns = descriptor.columns;
+    let samples_per_pixel = descriptor.samples_per_pixel;
+    let bytes_per_sample = descriptor.bytes_per_sample();
+
+    // the sample, shift and sign bit mask of each segment.  The segments of
+    // each sample are stored most significant byte first
+    let layout = |segment_index: usize| {
+        let significance = bytes_per_sample - 1 - segment_index % bytes_per_sample;
+        let mask = if signed && significance == bytes_per_sample - 1 {
+            0x80
+        } else {
+            0
+        };
+        (segment_index / bytes_per_sample, significance * 8, mask)
+    };
+
+    let mut sums = vec![0u64; thumbnail.segment_length() * samples_per_pixel];
+    let mut walked = Vec::new();
+    let mut diagnostics = DecodeDiagnostics::new();
+    for (segment_index, segment) in segments.iter().enumerate() {
+        let (sample, shift, mask) = layout(segment_index);
+        let result = for_each_span(
+            segment,
+            columns,
+            descriptor.segment_length(),
+            0..descriptor.segment_length(),
+            |row, column, span| {
+                let first_pixel = row / factor * thumbnail.columns;
+                let span_end = column + span.len();
+                let mut block = column / factor;
+                let mut start = column;
+                while start < span_end {
+                    let end = ((block + 1) * factor).min(span_end);
+                    // the values of a block are at most factor bytes so they
+                    // are summed before being shifted into place
+                    let total = match span {
+                        Span::Literal(values) => values[start - column..end - column]
+                            .iter()
+                            .map(|value| (value ^ mask) as u64)
+                            .sum(),
+                        Span::Replicate(value, _) => ((value ^ mask) as u64) * (end - start) as u64,
+                    };
+                    let sum = &mut sums[(first_pixel + block) * samples_per_pixel + sample];
+                    *sum = sum.saturating_add(total << shift);
+                    block += 1;
+                    start = end;
+                }
+            },
+        );
+
+        walked.push(result.bytes_decoded);
+        diagnostics.decode_segment_results[segment_index] = Some(result);
+    }
+
+    // the average of a sample is only written if every segment of the sample
+    // was decoded past the end of the block
+    let mut bytes_decoded = vec![0; segment_count];
+    for pixel in 0..thumbnail.segment_length() {
+        let first_row = pixel / thumbnail.columns * factor;
+        let first_column = pixel % thumbnail.columns * factor;
+        let block_rows = (first_row + factor).min(descriptor.rows) - first_row;
+        let block_columns = (first_column + factor).min(columns) - first_column;
+        let count = (block_rows * block_columns) as u64;
+        let last_pixel = (first_row + block_rows - 1) * columns + first_column + block_columns - 1;
+
+        for sample in 0..samples_per_pixel {
+            let sample_segments = sample * bytes_per_sample..(sample + 1) * bytes_per_sample;
+            if walked[sample_segments.clone()]
+                .iter()
+                .any(|walked| *walked <= last_pixel)
+            {
+                continue;
+            }
+            let average =
+                sums[pixel * samples_per_pixel + sample].saturating_add(count / 2) / count;
+            for segment_index in sample_segments {
+                let (_, shift, mask) = layout(segment_index);
+                let start_index = calculate_start_index(segment_count, segment_index);
+                decoded[pixel * segment_count + start_index] = (average >> shift) as u8 ^ mask;
+                bytes_decoded[segment_index] += 1;
+            }
+        }
+    }
+
+    for (segment_index, result) in diagnostics.decode_segment_results[..segment_count]
+        .iter_mut()
+        .enumerate()
+    {
+        if let Some(result) = result {
+            result.bytes_decoded = bytes_decoded[segment_index];
+        }
+        if bytes_decoded[segment_index] != thumbnail.segment_length() {
+            diagnostics.incomplete_decode = true;
+        }
+    }
+    diagnostics
+}
+
+/// Decodes a thumbnail of a DICOM RLE Image into a new buffer laid out like
+/// the output of decode() for an image with the layout returned by
+/// `thumbnail_descriptor()`.  Works with every segment layout decode()
+/// supports, although averaging is limited to samples of up to 32 bits.
+/// Returns an error if the factor is zero or the number of segments in the
+/// header does not match the descriptor.  As with decode(),
+/// problems in the segments are reported in the diagnostics and the pixels
+/// that could not be decoded are left as zero.
+///
+/// # Arguments
+///
+/// * `encoded`    - The encoded RLE image
+///
+/// * `descriptor` - The layout of the image
+///
+/// * `factor`     - The downsampling factor, see `thumbnail_factor()`
+///
+/// * `downsample` - How each block of pixels is reduced to one pixel
+///
+pub fn decode_thumbnail(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    factor: usize,
+    downsample: Downsample,
+) -> Result<(Vec<u8>, DecodeDiagnostics), Error> {
+    if factor == 0 {
+        return Err(Error::Format(
+            "the downsampling factor must be at least 1".to_owned(),
+        ));
+    }
+    if downsample != Downsample::Nearest && descriptor.bytes_per_sample() > 4 {
+        return Err(Error::Format(format!(
+            "cannot average samples with {} bits allocated, the most is 32",
+            descriptor.bits_allocated
+        )));
+    }
+    checked_decoded_length(descriptor)?;
+    check_segment_count(encoded, descriptor)?;
+
+    // a factor larger than the image makes the same one pixel thumbnail
+    let factor = factor.min(descriptor.rows.max(descriptor.columns).max(1));
+    let thumbnail = thumbnail_descriptor(descriptor, factor);
+    let mut decoded = vec![0; thumbnail.decoded_length()];
+    if decoded.is_empty() {
+        return Ok((decoded, DecodeDiagnostics::new()));
+    }
+
+    let segments = get_segments(encoded)?;
+    let diagnostics = match downsample {
+        Downsample::Nearest => {
+            decode_nearest(&segments, descriptor, &thumbnail, factor, &mut decoded)
+        }
+        Downsample::Average | Downsample::AverageSigned => {
+            let signed = downsample == Downsample::AverageSigned;
+            decode_average(
+                &segments,
+                descriptor,
+                &thumbnail,
+                factor,
+                signed,
+                &mut decoded,
+            )
+        }
+    };
+
+    Ok((decoded, diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_thumbnail, thumbnail_descriptor, thumbnail_factor, Downsample};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::test::tests::make_image;
+
+    // 3 rows of 5 columns of 8 bit values 0..15
+    const SEGMENTS: [&[u8]; 1] = [&[14, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14]];
+
+    #[test]
+    fn descriptor_and_factor() {
+        let descriptor = ImageDescriptor::new(512, 300, 3, 8);
+        let thumbnail = thumbnail_descriptor(&descriptor, 3);
+        assert_eq!(ImageDescriptor::new(171, 100, 3, 8), thumbnail);
+        assert_eq!(4, thumbnail_factor(&descriptor, 128));
+        assert_eq!(1, thumbnail_factor(&descriptor, 1000));
+    }
+
+    #[test]
+    fn nearest() {
+        let descriptor = ImageDescriptor::new(3, 5, 1, 8);
+        let (decoded, diagnostics) =
+            decode_thumbnail(&make_image(&SEGMENTS), &descriptor, 2, Downsample::Nearest).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert_eq!(vec![0, 2, 4, 10, 12, 14], decoded);
+    }
+
+    #[test]
+    fn nearest_stops_after_the_last_row_kept() {
+        let descriptor = ImageDescriptor::new(3, 5, 1, 8);
+        let mut encoded = make_image(&SEGMENTS);
+        encoded.extend_from_slice(&[128, 1, 2]);
+        let (_, diagnostics) =
+            decode_thumbnail(&encoded, &descriptor, 3, Downsample::Nearest).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        assert!(
+            !diagnostics.decode_segment_results[0]
+                .unwrap()
+                .invalid_prefix
+        );
+    }
+
+    #[test]
+    fn average() {
+        let descriptor = ImageDescriptor::new(3, 5, 1, 8);
+        let (decoded, diagnostics) =
+            decode_thumbnail(&make_image(&SEGMENTS), &descriptor, 2, Downsample::Average).unwrap();
+        assert!(!diagnostics.incomplete_decode);
+        // (0 + 1 + 5 + 6) / 4 = 3 and (4 + 9) / 2 = 6.5 rounds up to 7
+        assert_eq!(vec![3, 5, 7, 11, 13, 14], decoded);
+    }
+
+    #[test]
+    fn average_sixteen_bit() {
+        // 1 row of 2 columns with the values 0x00ff and 0x0101
+        let encoded = make_image(&[&[1, 0x00, 0x01, 0], &[1, 0xff, 0x01, 0]]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 16);
+        let (decoded, _) = decode_thumbnail(&encoded, &descriptor, 2, Downsample::Average).unwrap();
+        assert_eq!(0x0100, u16::from_le_bytes([decoded[0], decoded[1]]));
+    }
+
+    #[test]
+    fn average_signed() {
+        // 1 row of 2 columns with the values -3 and 1
+        let encoded = make_image(&[&[1, 0xff, 0x00, 0], &[1, 0xfd, 0x01, 0]]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 16);
+        let (decoded, _) =
+            decode_thumbnail(&encoded, &descriptor, 2, Downsample::AverageSigned).unwrap();
+        assert_eq!(-1, i16::from_le_bytes([decoded[0], decoded[1]]));
+
+        let (decoded, _) = decode_thumbnail(&encoded, &descriptor, 2, Downsample::Average).unwrap();
+        assert_eq!(0x7fff, u16::from_le_bytes([decoded[0], decoded[1]]));
+    }
+
+    #[test]
+    fn average_of_truncated_image_is_incomplete() {
+        let descriptor = ImageDescriptor::new(3, 5, 1, 8);
+        let encoded = make_image(&[&[9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]]);
+        let (decoded, diagnostics) =
+            decode_thumbnail(&encoded, &descriptor, 2, Downsample::Average).unwrap();
+        assert!(diagnostics.incomplete_decode);
+        assert_eq!(
+            3,
+            diagnostics.decode_segment_results[0].unwrap().bytes_decoded
+        );
+        assert_eq!(vec![3, 5, 7, 0, 0, 0], decoded);
+    }
+
+    #[test]
+    fn zero_factor_is_an_error() {
+        let descriptor = ImageDescriptor::new(3, 5, 1, 8);
+        let result = decode_thumbnail(&make_image(&SEGMENTS), &descriptor, 0, Downsample::Nearest);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn average_of_wide_samples_is_an_error() {
+        let encoded = make_image(&[&[0u8, 1][..]; 5]);
+        let descriptor = ImageDescriptor::new(1, 1, 1, 40);
+        let result = decode_thumbnail(&encoded, &descriptor, 1, Downsample::Average);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
@@ -0,0 +1,191 @@
+//! Parsing of the runs in an RLE segment without decoding them.  This is the
+//! one place the control byte rules are implemented, decode_segment(), the
+//! decode strategies and inspection are all built on it.
+
+use std::iter::FusedIterator;
+
+/// One run of an RLE segment, or the reason parsing stopped
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Token {
+    /// a literal run of `len` bytes stored at `segment[offset..offset + len]`
+    Literal { offset: usize, len: usize },
+
+    /// a replicated run of `len` copies of `value`
+    Replicate { value: u8, len: usize },
+
+    /// the control byte at `position` is 128, which is illegal as per the
+    /// DICOM standard
+    Invalid { position: usize },
+
+    /// the literal run with its control byte at `position` is longer than
+    /// the rest of the segment
+    LiteralUnderflow { position: usize },
+
+    /// the replicated run with its control byte at `position` is missing its
+    /// value
+    ReplicateUnderflow { position: usize },
+}
+
+impl Token {
+    /// Returns the number of bytes the token decodes to, zero for the tokens
+    /// that stop parsing
+    pub fn decoded_len(&self) -> usize {
+        match self {
+            Token::Literal { len, .. } | Token::Replicate { len, .. } => *len,
+            _ => 0,
+        }
+    }
+}
+
+/// Iterator over the tokens of an RLE segment, see `tokens()`
+#[derive(Clone, Debug)]
+pub struct Tokens<'a> {
+    // truncated at the control byte that stopped parsing
+    segment: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    /// Returns the offset of the next control byte, which is the number of
+    /// encoded bytes consumed by the runs returned so far.  After an error
+    /// token this is the position of the control byte that could not be
+    /// parsed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token;
+
+    #[inline]
+    fn next(&mut self) -> Option<Token> {
+        if self.position >= self.segment.len() {
+            return None;
+        }
+
+        let position = self.position;
+        let control = self.segment[position];
+        let run_index = position + 1;
+        let remaining = self.segment.len() - run_index;
+
+        if control <= 127 {
+            // literal run of values
+            let len = control as usize + 1;
+            if len <= remaining {
+                self.position = run_index + len;
+                return Some(Token::Literal {
+                    offset: run_index,
+                    len,
+                });
+            }
+            self.segment = &self.segment[..position];
+            Some(Token::LiteralUnderflow { position })
+        } else if control > 128 {
+            // replicated run of values
+            if remaining > 0 {
+                self.position = run_index + 1;
+                return Some(Token::Replicate {
+                    value: self.segment[run_index],
+                    len: 257 - control as usize,
+                });
+            }
+            self.segment = &self.segment[..position];
+            Some(Token::ReplicateUnderflow { position })
+        } else {
+            // a control value of 128 is illegal as per the DICOM standard
+            // http://dicom.nema.org/medical/Dicom/2016e/output/chtml/part05/sect_G.3.html
+            self.segment = &self.segment[..position];
+            Some(Token::Invalid { position })
+        }
+    }
+}
+
+impl<'a> FusedIterator for Tokens<'a> {}
+
+/// Returns an iterator over the runs of an RLE segment.  Iteration ends at the
+/// end of the segment or after the first `Invalid`, `LiteralUnderflow` or
+/// `ReplicateUnderflow` token.
+///
+/// # Arguments
+///
+/// * `segment` - The encoded RLE segment
+///
+pub fn tokens(segment: &[u8]) -> Tokens<'_> {
+    Tokens {
+        segment,
+        position: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokens, Token};
+
+    #[test]
+    fn literal_and_replicate() {
+        let segment = [1, 7, 8, 253, 9, 0, 10];
+        let mut iterator = tokens(&segment);
+        assert_eq!(Some(Token::Literal { offset: 1, len: 2 }), iterator.next());
+        assert_eq!(3, iterator.position());
+        assert_eq!(Some(Token::Replicate { value: 9, len: 4 }), iterator.next());
+        assert_eq!(Some(Token::Literal { offset: 6, len: 1 }), iterator.next());
+        assert_eq!(None, iterator.next());
+        assert_eq!(7, iterator.position());
+    }
+
+    #[test]
+    fn longest_runs() {
+        let mut segment = vec![127];
+        segment.extend_from_slice(&[0; 128]);
+        segment.extend_from_slice(&[129, 5]);
+        let found: Vec<Token> = tokens(&segment).collect();
+        assert_eq!(
+            vec![
+                Token::Literal {
+                    offset: 1,
+                    len: 128
+                },
+                Token::Replicate { value: 5, len: 128 }
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn stops_at_invalid_control_byte() {
+        let segment = [0, 7, 128, 255, 9];
+        let mut iterator = tokens(&segment);
+        assert_eq!(Some(Token::Literal { offset: 1, len: 1 }), iterator.next());
+        assert_eq!(Some(Token::Invalid { position: 2 }), iterator.next());
+        assert_eq!(None, iterator.next());
+        assert_eq!(2, iterator.position());
+    }
+
+    #[test]
+    fn underflows() {
+        let found: Vec<Token> = tokens(&[255, 1, 2, 3]).collect();
+        assert_eq!(
+            vec![
+                Token::Replicate { value: 1, len: 2 },
+                Token::LiteralUnderflow { position: 2 }
+            ],
+            found
+        );
+
+        let mut iterator = tokens(&[255]);
+        assert_eq!(
+            Some(Token::ReplicateUnderflow { position: 0 }),
+            iterator.next()
+        );
+        assert_eq!(None, iterator.next());
+        assert_eq!(0, iterator.position());
+    }
+
+    #[test]
+    fn decoded_len() {
+        assert_eq!(3, Token::Literal { offset: 1, len: 3 }.decoded_len());
+        assert_eq!(2, Token::Replicate { value: 0, len: 2 }.decoded_len());
+        assert_eq!(0, Token::Invalid { position: 0 }.decoded_len());
+    }
+}
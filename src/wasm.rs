@@ -0,0 +1,237 @@
+use crate::decode::{decode_frame, decode_frame_i16, decode_frame_u16};
+use crate::descriptor::ImageDescriptor;
+use crate::diagnostics::DecodeDiagnostics;
+use crate::error::Error;
+use crate::stored_bits::{
+    decode_frame_i16_stored, decode_frame_u16_stored, optional_stored_bits, StoredBits,
+};
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+// The functions below do the work and can be tested natively.  The exported
+// functions at the bottom of the file convert their results to JS values.
+
+/// Decodes an RLE image with any number of bits allocated into bytes
+pub fn decode_uint8(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+) -> Result<(Vec<u8>, DecodeDiagnostics), Error> {
+    decode_frame(encoded, descriptor)
+}
+
+/// Decodes a 16 bit signed grayscale RLE image, normalizing the samples if
+/// the stored bits are given
+pub fn decode_int16(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    stored: Option<&StoredBits>,
+) -> Result<(Vec<i16>, DecodeDiagnostics), Error> {
+    match stored {
+        Some(stored) => decode_frame_i16_stored(encoded, descriptor, stored),
+        None => decode_frame_i16(encoded, descriptor),
+    }
+}
+
+/// Decodes a 16 bit unsigned grayscale RLE image, normalizing the samples if
+/// the stored bits are given
+pub fn decode_uint16(
+    encoded: &[u8],
+    descriptor: &ImageDescriptor,
+    stored: Option<&StoredBits>,
+) -> Result<(Vec<u16>, DecodeDiagnostics), Error> {
+    match stored {
+        Some(stored) => decode_frame_u16_stored(encoded, descriptor, stored),
+        None => decode_frame_u16(encoded, descriptor),
+    }
+}
+
+fn set(object: &Object, key: &str, value: JsValue) {
+    // setting a property on a plain object cannot fail
+    Reflect::set(object, &JsValue::from_str(key), &value).unwrap();
+}
+
+// converts diagnostics to a plain JS object:
+// { incompleteDecode, segments: [{ bytesDecoded, bytesConsumed, literalRunUnderflow,
+//   replicatedRunUnderflow, decodedOverflow, invalidPrefix }] }
+fn diagnostics_to_js(diagnostics: &DecodeDiagnostics) -> Object {
+    let segments = Array::new();
+    for result in diagnostics.decode_segment_results.iter().flatten() {
+        let segment = Object::new();
+        set(
+            &segment,
+            "bytesDecoded",
+            (result.bytes_decoded as f64).into(),
+        );
+        set(
+            &segment,
+            "bytesConsumed",
+            (result.bytes_consumed as f64).into(),
+        );
+        set(
+            &segment,
+            "literalRunUnderflow",
+            result.literal_run_underflow.into(),
+        );
+        set(
+            &segment,
+            "replicatedRunUnderflow",
+            result.replicated_run_underflow.into(),
+        );
+        set(&segment, "decodedOverflow", result.decoded_overflow.into());
+        set(&segment, "invalidPrefix", result.invalid_prefix.into());
+        segments.push(&segment);
+    }
+
+    let object = Object::new();
+    set(
+        &object,
+        "incompleteDecode",
+        diagnostics.incomplete_decode.into(),
+    );
+    set(&object, "segments", segments.into());
+    object
+}
+
+fn to_js<T: Into<JsValue>>(
+    result: Result<(T, DecodeDiagnostics), Error>,
+) -> Result<Object, JsError> {
+    let (pixels, diagnostics) = result.map_err(|error| JsError::new(&error.to_string()))?;
+    let object = Object::new();
+    set(&object, "pixels", pixels.into());
+    set(
+        &object,
+        "diagnostics",
+        diagnostics_to_js(&diagnostics).into(),
+    );
+    Ok(object)
+}
+
+fn to_descriptor(
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    bits_allocated: usize,
+) -> ImageDescriptor {
+    ImageDescriptor::new(rows, columns, samples_per_pixel, bits_allocated)
+}
+
+/// Decodes an RLE image and returns `{ pixels: Uint8Array, diagnostics }`
+#[wasm_bindgen(js_name = decode)]
+pub fn decode_js(
+    encoded: &[u8],
+    rows: usize,
+    columns: usize,
+    samples_per_pixel: usize,
+    bits_allocated: usize,
+) -> Result<Object, JsError> {
+    let descriptor = to_descriptor(rows, columns, samples_per_pixel, bits_allocated);
+    to_js(decode_uint8(encoded, &descriptor))
+}
+
+/// Decodes a 16 bit signed grayscale RLE image and returns `{ pixels: Int16Array, diagnostics }`.
+/// If `bitsStored` or `highBit` is given the samples are masked and sign
+/// extended, with `pixelRepresentation` defaulting to 1.
+#[wasm_bindgen(js_name = decodeInt16)]
+pub fn decode_int16_js(
+    encoded: &[u8],
+    rows: usize,
+    columns: usize,
+    #[wasm_bindgen(js_name = bitsStored)] bits_stored: Option<usize>,
+    #[wasm_bindgen(js_name = highBit)] high_bit: Option<usize>,
+    #[wasm_bindgen(js_name = pixelRepresentation)] pixel_representation: Option<usize>,
+) -> Result<Object, JsError> {
+    let descriptor = to_descriptor(rows, columns, 1, 16);
+    let stored = optional_stored_bits(bits_stored, high_bit, pixel_representation.unwrap_or(1));
+    to_js(decode_int16(encoded, &descriptor, stored.as_ref()))
+}
+
+/// Decodes a 16 bit unsigned grayscale RLE image and returns `{ pixels: Uint16Array, diagnostics }`.
+/// If `bitsStored` or `highBit` is given the samples are masked, with
+/// `pixelRepresentation` defaulting to 0.
+#[wasm_bindgen(js_name = decodeUint16)]
+pub fn decode_uint16_js(
+    encoded: &[u8],
+    rows: usize,
+    columns: usize,
+    #[wasm_bindgen(js_name = bitsStored)] bits_stored: Option<usize>,
+    #[wasm_bindgen(js_name = highBit)] high_bit: Option<usize>,
+    #[wasm_bindgen(js_name = pixelRepresentation)] pixel_representation: Option<usize>,
+) -> Result<Object, JsError> {
+    let descriptor = to_descriptor(rows, columns, 1, 16);
+    let stored = optional_stored_bits(bits_stored, high_bit, pixel_representation.unwrap_or(0));
+    to_js(decode_uint16(encoded, &descriptor, stored.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_int16, decode_uint16, decode_uint8};
+    use crate::descriptor::ImageDescriptor;
+    use crate::error::Error;
+    use crate::stored_bits::StoredBits;
+    use crate::test::tests::make_header;
+
+    fn make_sixteen_bit_image() -> Vec<u8> {
+        // 3 pixels, msb segment is [0x80, 0x01, 0x01] and lsb segment is
+        // [0x00, 0x02, 0x03]
+        let mut encoded = make_header(&mut vec![2, 64, 68]);
+        encoded.extend_from_slice(&[0, 0x80, 255, 0x01, 2, 0x00, 0x02, 0x03]);
+        encoded
+    }
+
+    #[test]
+    fn decodes_int16() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let (pixels, diagnostics) =
+            decode_int16(&make_sixteen_bit_image(), &descriptor, None).unwrap();
+        assert_eq!(vec![-32768, 0x0102, 0x0103], pixels);
+        assert!(!diagnostics.incomplete_decode);
+    }
+
+    #[test]
+    fn decodes_uint16() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let (pixels, _) = decode_uint16(&make_sixteen_bit_image(), &descriptor, None).unwrap();
+        assert_eq!(vec![0x8000, 0x0102, 0x0103], pixels);
+    }
+
+    #[test]
+    fn decodes_with_stored_bits() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let stored = StoredBits::new(9, 8, 1);
+        let (pixels, _) =
+            decode_int16(&make_sixteen_bit_image(), &descriptor, Some(&stored)).unwrap();
+        assert_eq!(vec![0, -254, -253], pixels);
+
+        let stored = StoredBits::new(8, 7, 0);
+        let (pixels, _) =
+            decode_uint16(&make_sixteen_bit_image(), &descriptor, Some(&stored)).unwrap();
+        assert_eq!(vec![0, 2, 3], pixels);
+    }
+
+    #[test]
+    fn decodes_uint8() {
+        let descriptor = ImageDescriptor::new(1, 3, 1, 16);
+        let (pixels, _) = decode_uint8(&make_sixteen_bit_image(), &descriptor).unwrap();
+        assert_eq!(vec![0x00, 0x80, 0x02, 0x01, 0x03, 0x01], pixels);
+    }
+
+    #[test]
+    fn decode_int16_of_eight_bit_image_is_an_error() {
+        let mut encoded = make_header(&mut vec![1, 64]);
+        encoded.extend_from_slice(&[1, 0, 0]);
+        let descriptor = ImageDescriptor::new(1, 2, 1, 8);
+
+        let result = decode_int16(&encoded, &descriptor, None);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn decode_uint16_of_color_image_is_an_error() {
+        let mut encoded = make_header(&mut vec![6, 64, 65, 66, 67, 68, 69]);
+        encoded.extend_from_slice(&[0; 6]);
+        let descriptor = ImageDescriptor::new(1, 1, 3, 16);
+
+        let result = decode_uint16(&encoded, &descriptor, None);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+}
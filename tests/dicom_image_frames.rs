@@ -1,7 +1,24 @@
 #[cfg(test)]
+// the original tests keep their style
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::identity_op,
+    clippy::needless_borrow,
+    clippy::slow_vector_initialization,
+    clippy::useless_format
+)]
 mod tests {
-    use dicomrle::decode::{decode, decode_i16, decode_u16};
+    use dicomrle::crop::crop_rows;
+    use dicomrle::decode::{decode, decode_frame, decode_i16, decode_into_vec, decode_u16};
+    use dicomrle::descriptor::ImageDescriptor;
     use dicomrle::error::Error;
+    use dicomrle::pitched::{decode_pitched, pitched_length};
+    use dicomrle::preview::decode_preview_u16;
+    use dicomrle::region::decode_region_frame;
+    use dicomrle::remap::remap_frame;
+    use dicomrle::statistics::statistics;
+    use dicomrle::stored_bits::{decode_frame_i16_stored, StoredBits};
+    use dicomrle::thumbnail::{decode_thumbnail, Downsample};
     use std::fs::File;
     use std::io::Read;
     use std::slice;
@@ -20,12 +37,13 @@ mod tests {
     {
         assert_eq!(a.len(), b.len());
         for i in 0..a.len() {
-            if a[1] != b[1] {
-                assert!(
-                    false,
-                    format!("difference found at position {} {}!={}", i, a[i], b[i])
-                );
-            }
+            assert!(
+                a[i] == b[i],
+                "difference found at position {} {}!={}",
+                i,
+                a[i],
+                b[i]
+            );
         }
     }
 
@@ -176,4 +194,292 @@ mod tests {
         let result = decode(&encoded, &mut decoded).unwrap();
         assert_eq!(result.incomplete_decode, true);
     }
+
+    #[test]
+    fn verify_us1_decode_frame() {
+        let encoded = read_file("tests/rleimage/us1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+
+        let (decoded, result) = decode_frame(&encoded, &descriptor).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/us1.raw").unwrap();
+        images_are_same(&decoded, &raw);
+    }
+
+    #[test]
+    fn verify_decode_frame_segment_count_mismatch_is_an_error() {
+        let encoded = read_file("tests/rleimage/us1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(480, 640, 1, 8);
+
+        let result = decode_frame(&encoded, &descriptor);
+        assert!(matches!(result, Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn verify_rf1_statistics() {
+        let encoded = read_file("tests/rleimage/rf1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 8);
+        let statistics = statistics(&encoded, &descriptor, 0).unwrap();
+
+        let raw = read_file("tests/rawimage/rf1.raw").unwrap();
+        let mut histogram = vec![0; 256];
+        for value in &raw {
+            histogram[*value as usize] += 1;
+        }
+        assert_eq!(histogram, statistics.histogram);
+        assert_eq!(512 * 512, statistics.pixel_count);
+        assert_eq!(raw.iter().min().map(|v| *v as i32), statistics.min);
+        assert_eq!(raw.iter().max().map(|v| *v as i32), statistics.max);
+    }
+
+    #[test]
+    fn verify_ct_statistics() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 16);
+        let unsigned = statistics(&encoded, &descriptor, 0).unwrap();
+        let signed = statistics(&encoded, &descriptor, 1).unwrap();
+
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        let values: Vec<u16> = raw
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        let mut histogram = vec![0; 65536];
+        for value in &values {
+            histogram[*value as usize] += 1;
+        }
+        assert_eq!(histogram, unsigned.histogram);
+        assert_eq!(512 * 512, unsigned.pixel_count);
+        assert_eq!(values.iter().min().map(|v| *v as i32), unsigned.min);
+        assert_eq!(values.iter().max().map(|v| *v as i32), unsigned.max);
+
+        assert_eq!(histogram, signed.histogram);
+        let min = values.iter().map(|v| *v as i16).min();
+        let max = values.iter().map(|v| *v as i16).max();
+        assert_eq!(min.map(|v| v as i32), signed.min);
+        assert_eq!(max.map(|v| v as i32), signed.max);
+    }
+
+    #[test]
+    fn verify_rf1_remap() {
+        let encoded = read_file("tests/rleimage/rf1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 8);
+        let mut lut = [0; 256];
+        for (index, value) in lut.iter_mut().enumerate() {
+            *value = (index as u8) / 64 * 64;
+        }
+
+        let remapped = remap_frame(&encoded, &descriptor, &lut).unwrap();
+        assert!(remapped.len() < encoded.len());
+        let (decoded, result) = decode_frame(&remapped, &descriptor).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/rf1.raw").unwrap();
+        let mapped: Vec<u8> = raw.iter().map(|value| lut[*value as usize]).collect();
+        images_are_same(&decoded, &mapped);
+    }
+
+    #[test]
+    fn verify_us1_crop_rows() {
+        let encoded = read_file("tests/rleimage/us1.rle").unwrap();
+        let cropped = crop_rows(&encoded, 640, 100..300).unwrap();
+
+        let descriptor = ImageDescriptor::new(200, 640, 3, 8);
+        let (decoded, result) = decode_frame(&cropped, &descriptor).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/us1.raw").unwrap();
+        images_are_same(&decoded, &raw[100 * 640 * 3..300 * 640 * 3]);
+    }
+
+    #[test]
+    fn verify_ct_crop_rows() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let cropped = crop_rows(&encoded, 512, 511..512).unwrap();
+
+        let descriptor = ImageDescriptor::new(1, 512, 1, 16);
+        let (decoded, result) = decode_frame(&cropped, &descriptor).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        images_are_same(&decoded, &raw[511 * 512 * 2..]);
+    }
+
+    // returns the bytes of a region of a raw image
+    fn raw_region(
+        raw: &[u8],
+        row_length: usize,
+        pixel_length: usize,
+        rows: std::ops::Range<usize>,
+        columns: std::ops::Range<usize>,
+    ) -> Vec<u8> {
+        let mut region = Vec::new();
+        for row in rows {
+            let start = row * row_length + columns.start * pixel_length;
+            region.extend_from_slice(&raw[start..start + columns.len() * pixel_length]);
+        }
+        region
+    }
+
+    #[test]
+    fn verify_ct_decode_region() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 16);
+        let (decoded, result) =
+            decode_region_frame(&encoded, &descriptor, 100..200, Some(50..300)).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        images_are_same(&decoded, &raw_region(&raw, 1024, 2, 100..200, 50..300));
+    }
+
+    #[test]
+    fn verify_us1_decode_region() {
+        let encoded = read_file("tests/rleimage/us1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let (decoded, result) =
+            decode_region_frame(&encoded, &descriptor, 240..480, Some(1..639)).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/us1.raw").unwrap();
+        images_are_same(&decoded, &raw_region(&raw, 640 * 3, 3, 240..480, 1..639));
+    }
+
+    #[test]
+    fn verify_ct_nearest_thumbnail() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 16);
+        let (decoded, result) =
+            decode_thumbnail(&encoded, &descriptor, 5, Downsample::Nearest).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        let mut expected = Vec::new();
+        for row in (0..512).step_by(5) {
+            for column in (0..512).step_by(5) {
+                let index = (row * 512 + column) * 2;
+                expected.extend_from_slice(&raw[index..index + 2]);
+            }
+        }
+        images_are_same(&decoded, &expected);
+    }
+
+    #[test]
+    fn verify_ct_sign_extended_twelve_bits() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 16);
+        let stored = StoredBits::new(12, 11, 1);
+        let (decoded, result) = decode_frame_i16_stored(&encoded, &descriptor, &stored).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        for (sample, value) in raw.chunks(2).zip(decoded) {
+            let twelve_bits = u16::from_le_bytes([sample[0], sample[1]]) & 0x0fff;
+            let expected = if twelve_bits & 0x0800 != 0 {
+                twelve_bits as i16 - 0x1000
+            } else {
+                twelve_bits as i16
+            };
+            assert_eq!(expected, value);
+        }
+    }
+
+    #[test]
+    fn verify_ct_decode_into_vec() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let mut decoded = Vec::new();
+        let result = decode_into_vec(&encoded, &mut decoded, 512 * 512 * 2).unwrap();
+        assert!(!result.incomplete_decode);
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        images_are_same(&decoded, &raw);
+
+        // only the first 1000 values of the least significant segment
+        let second_segment = u32::from_le_bytes([encoded[8], encoded[9], encoded[10], encoded[11]]);
+        let mut truncated = encoded.clone();
+        let mut values = 0;
+        let mut position = second_segment as usize;
+        while values < 1000 {
+            let control = truncated[position] as usize;
+            if control < 128 {
+                values += control + 1;
+                position += control + 2;
+            } else {
+                values += 257 - control;
+                position += 2;
+            }
+        }
+        truncated.truncate(position);
+        let mut decoded = Vec::new();
+        let result = decode_into_vec(&truncated, &mut decoded, 512 * 512 * 2).unwrap();
+        assert!(result.incomplete_decode);
+        assert_eq!(values * 2, decoded.len());
+        images_are_same(&decoded, &raw[..values * 2]);
+    }
+
+    #[test]
+    fn verify_us1_decode_pitched() {
+        let encoded = read_file("tests/rleimage/us1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        // rows of 640 RGBA pixels padded to 256 bytes
+        let row_pitch = 2816;
+        let mut decoded = vec![0; pitched_length(&descriptor, row_pitch, Some(4)).unwrap()];
+        let result =
+            decode_pitched(&encoded, &descriptor, &mut decoded, row_pitch, Some(4)).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/us1.raw").unwrap();
+        for (row, raw_row) in raw.chunks(640 * 3).enumerate() {
+            let pitched_row = &decoded[row * row_pitch..];
+            for (column, pixel) in raw_row.chunks(3).enumerate() {
+                assert_eq!(pixel, &pitched_row[column * 4..column * 4 + 3]);
+            }
+        }
+    }
+
+    #[test]
+    fn verify_ct_preview_from_first_segment() {
+        let encoded = read_file("tests/rleimage/ct.rle").unwrap();
+        let descriptor = ImageDescriptor::new(512, 512, 1, 16);
+        // keep the header and the most significant segment only
+        let second_segment = u32::from_le_bytes([encoded[8], encoded[9], encoded[10], encoded[11]]);
+        let (decoded, result) =
+            decode_preview_u16(&encoded[..second_segment as usize], &descriptor).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/ct.raw").unwrap();
+        let expected: Vec<u16> = raw
+            .chunks(2)
+            .map(|sample| (sample[1] as u16) << 8)
+            .collect();
+        assert!(decoded == expected);
+    }
+
+    #[test]
+    fn verify_us1_average_thumbnail() {
+        let encoded = read_file("tests/rleimage/us1.rle").unwrap();
+        let descriptor = ImageDescriptor::new(480, 640, 3, 8);
+        let (decoded, result) =
+            decode_thumbnail(&encoded, &descriptor, 3, Downsample::Average).unwrap();
+        assert!(!result.incomplete_decode);
+
+        let raw = read_file("tests/rawimage/us1.raw").unwrap();
+        let mut expected = Vec::new();
+        for row in (0..480).step_by(3) {
+            for column in (0..640).step_by(3) {
+                for sample in 0..3 {
+                    let mut sum = 0;
+                    let mut count = 0;
+                    for block_row in row..(row + 3).min(480) {
+                        for block_column in column..(column + 3).min(640) {
+                            sum += raw[(block_row * 640 + block_column) * 3 + sample] as usize;
+                            count += 1;
+                        }
+                    }
+                    expected.push(((sum + count / 2) / count) as u8);
+                }
+            }
+        }
+        images_are_same(&decoded, &expected);
+    }
 }